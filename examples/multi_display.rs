@@ -0,0 +1,65 @@
+//! Shows a bar on every display given on the command line, e.g.
+//! `cargo run --example multi_display -- :0 :1`.
+
+use neo_bar::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmSurface};
+use neo_bar::config::{BarBuilder, DockDirection};
+use neo_bar::draw::{Color, Painter, Rect};
+use neo_bar::event;
+use neo_bar::surface::Surface;
+
+struct DisplayBar {
+    display: String,
+}
+
+impl Bar for DisplayBar {
+    fn new() -> Self {
+        Self {
+            display: std::env::var("DISPLAY").unwrap_or_default(),
+        }
+    }
+
+    fn get_bar_builder(&self) -> BarBuilder {
+        BarBuilder::default()
+            .title_owned(format!("bar on {}", self.display))
+            .display_owned(self.display.clone())
+            .docking(DockDirection::Top)
+            .width(24)
+    }
+
+    fn get_event_types(&self) -> event::EventTypes {
+        event::QUIT
+    }
+
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        damage: Rect,
+    ) {
+        let (w, h) = bar.get_size();
+        let mut surface = Surface::new(w, h);
+        Painter::new(&mut surface).clear(Color::rgb(0x73, 0x27, 0xf3));
+        let surface = <Wm as WmAdapter<Self>>::Surface::from_surface(surface);
+        if let Err(e) = bar.blit_rect(&surface, damage) {
+            println!("failed to draw bar on {}: {}", self.display, e);
+        }
+    }
+}
+
+fn main() {
+    let threads = std::env::args()
+        .skip(1)
+        .map(|display| {
+            std::thread::spawn(move || {
+                let bar = DisplayBar {
+                    display: display.clone(),
+                };
+                if let Err(e) = neo_bar::bar::run_bar::<_, neo_bar::x11::X11RustAdapter<_>>(bar) {
+                    println!("\x1b[1;31mfatal error on {}: {}", display, e);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        let _ = thread.join();
+    }
+}
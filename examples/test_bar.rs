@@ -1,12 +1,107 @@
-use neo_bar::bar::{Bar, WmAdapterExt, WmAdapterGetBar};
+use neo_bar::bar::{Bar, WmAdapterBar, WmAdapterExt, WmAdapterGetBar};
 use neo_bar::config::{BarBuilder, DockDirection, ZIndex};
+use neo_bar::draw::{Color, Painter, Rect};
 use neo_bar::event;
+use neo_bar::module::{Module, ModuleContainer, ModuleEvent, ModuleId, Section, Update};
+use neo_bar::surface::Surface;
+use neo_bar::timer::{Schedule, TimerId, Timers};
+use std::time::Duration;
 
-pub struct TestBar;
+struct Block {
+    color: Color,
+    length: u32,
+    active: bool,
+    hovered: bool,
+}
+
+impl Module for Block {
+    fn measure(&mut self, _thickness: u32) -> u32 {
+        self.length
+    }
+
+    fn draw(&mut self, painter: &mut Painter<Surface>) {
+        let rect = painter.bounds().inset(4);
+        let color = if self.hovered {
+            self.color.with_alpha(0xc0)
+        } else {
+            self.color
+        };
+        painter.fill_rounded_rect(rect, 6, color);
+        if self.active {
+            painter.underline(rect, 2, Color::WHITE);
+        }
+    }
+
+    fn on_event(&mut self, event: &ModuleEvent) -> Update {
+        match event {
+            ModuleEvent::Click(ev) if !ev.pressed && ev.button == event::MouseButton::Left => {
+                self.active = !self.active;
+                Update::Redraw
+            }
+            ModuleEvent::Scroll(ev) => {
+                let step = if ev.modifiers & event::MOD_SHIFT != 0 {
+                    16
+                } else {
+                    4
+                };
+                self.length = match ev.direction {
+                    event::ScrollDirection::Up => self.length + step,
+                    _ => self.length.saturating_sub(step).max(16),
+                };
+                Update::Resize
+            }
+            ModuleEvent::DoubleClick(_) => {
+                self.length *= 2;
+                Update::Resize
+            }
+            ModuleEvent::Timer(_) => {
+                self.active = !self.active;
+                Update::Redraw
+            }
+            ModuleEvent::HoverEnter | ModuleEvent::HoverLeave => {
+                self.hovered = matches!(event, ModuleEvent::HoverEnter);
+                Update::Redraw
+            }
+            _ => Update::None,
+        }
+    }
+}
+
+pub struct TestBar {
+    modules: ModuleContainer,
+    blinker: ModuleId,
+}
+
+impl TestBar {
+    fn redraw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+    ) {
+        if self.modules.needs_redraw() {
+            if let Err(e) = self.modules.render::<Self, Wm>(bar) {
+                println!("failed to draw bar: {}", e);
+            }
+        }
+    }
+}
 
 impl Bar for TestBar {
     fn new() -> Self {
-        Self
+        let mut modules = ModuleContainer::new(DockDirection::Bottom)
+            .padding(4)
+            .spacing(4)
+            .background(Color::rgba(0x20, 0x20, 0x20, 0xc0));
+        let block = |color, length| Block {
+            color,
+            length,
+            active: false,
+            hovered: false,
+        };
+        modules.push(Section::Left, block(Color::rgb(0x73, 0x27, 0xf3), 80));
+        modules.push(Section::Left, block(Color::rgb(0xa3, 0x11, 0x11), 40));
+        let blinker = modules.push(Section::Center, block(Color::WHITE, 120));
+        modules.push(Section::Right, block(Color::rgb(0x11, 0xa3, 0x11), 60));
+        Self { modules, blinker }
     }
 
     fn get_bar_builder(&self) -> BarBuilder {
@@ -20,15 +115,80 @@ impl Bar for TestBar {
     }
 
     fn get_event_types(&self) -> event::EventTypes {
-        event::CLICK | event::QUIT
+        event::CLICK | event::SCROLL | event::MOUSE_MOVE | event::HOVER | event::QUIT
+    }
+
+    fn on_start(&mut self, timers: &mut Timers) {
+        let timer = timers.add(Schedule::Aligned(Duration::from_secs(1)));
+        self.modules.subscribe_timer(self.blinker, timer);
+    }
+
+    fn on_timer(&mut self, _timers: &mut Timers, id: TimerId) -> Update {
+        self.modules.dispatch_timer(id)
+    }
+
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        damage: Rect,
+    ) {
+        if let Err(e) = self.modules.render_damage::<Self, Wm>(bar, damage) {
+            println!("failed to draw bar: {}", e);
+        }
+    }
+
+    fn on_resize<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::ResizeEvent,
+    ) {
+        self.modules.set_docking(bar.get_docking());
     }
 
     fn on_click<'a, Wm: WmAdapterExt<Self>>(
         &mut self,
-        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
         event: event::ClickEvent,
     ) {
         println!("click {:?}", event);
+        self.modules.dispatch_click(&event);
+        self.redraw::<Wm>(bar);
+    }
+
+    fn on_double_click<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        event: event::ClickEvent,
+    ) {
+        self.modules.dispatch_double_click(&event);
+        self.redraw::<Wm>(bar);
+    }
+
+    fn on_scroll<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        event: event::ScrollEvent,
+    ) {
+        self.modules.dispatch_scroll(&event);
+        self.redraw::<Wm>(bar);
+    }
+
+    fn on_mouse_move<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        event: event::MotionEvent,
+    ) {
+        self.modules.dispatch_motion(&event);
+        self.redraw::<Wm>(bar);
+    }
+
+    fn on_pointer_leave<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::MotionEvent,
+    ) {
+        self.modules.dispatch_leave();
+        self.redraw::<Wm>(bar);
     }
 
     fn on_quit(&mut self) {
@@ -1,28 +1,74 @@
-use super::config::{BarBuilder, DockDirection};
+use super::config::{BarBuilder, BarInstance, DockDirection, ZIndex};
+use super::draw::Rect;
 use super::error::RunnerError;
 use super::event;
+use super::module::Update;
+use super::timer::{TimerId, Timers};
+use core::task::{Context, Poll};
+use std::time::Instant;
 
 pub trait WmScreen {
     fn dimensions(&self) -> (u32, u32);
     fn physical_dimensions(&self) -> Option<(f32, f32)>;
+    fn position(&self) -> (i32, i32);
+    fn name(&self) -> Option<&str>;
+    fn is_primary(&self) -> bool;
+}
+
+pub trait WmSurface: Sized {
+    fn new(width: u32, height: u32) -> Self;
+    fn dimensions(&self) -> (u32, u32);
+    fn pixels(&self) -> &[u32];
+    fn pixels_mut(&mut self) -> &mut [u32];
+    fn from_surface(surface: crate::surface::Surface) -> Self {
+        let mut slf = Self::new(surface.width(), surface.height());
+        slf.pixels_mut().copy_from_slice(surface.data());
+        slf
+    }
+    fn into_surface(self) -> crate::surface::Surface {
+        let (w, h) = self.dimensions();
+        let mut surface = crate::surface::Surface::new(w, h);
+        surface.data_mut().copy_from_slice(self.pixels());
+        surface
+    }
 }
 
 pub trait WmAdapter<B: Bar>: Sized {
     type Error: std::error::Error;
-    type Surface;
-    type Screen: WmScreen;
+    type Surface: WmSurface;
+    type Screen: WmScreen + Clone + PartialEq;
+    /// Whether the window manager places bars on the same edge after each
+    /// other by itself, so their offsets must not be stacked.
+    const STACKS_EXCLUSIVE_ZONES: bool = false;
     fn new(cfg: &BarBuilder) -> Result<Self, Self::Error>;
     fn get_screen_count(&self) -> usize;
-    fn get_screen(&self, n: usize) -> Option<&Self::Screen>;
+    fn get_screen(&self, n: usize) -> Option<Self::Screen>;
     fn await_event(&self) -> Result<event::Event, Self::Error>;
     fn poll_event(&self) -> Result<core::task::Poll<event::Event>, Self::Error>;
+    fn get_fd(&self) -> std::os::unix::io::RawFd;
 }
 
 pub trait WmAdapterBar<'a, B: Bar, Wm: WmAdapter<B>>: Sized {
     fn new(bar: &B, wm: &'a Wm, cfg: &BarBuilder, screen: &Wm::Screen) -> Result<Self, Wm::Error>;
+    /// Docks the bar to another edge. The bar is notified through
+    /// `Bar::on_resize` once the window was resized.
     fn set_docking(&mut self, dir: DockDirection) -> Result<(), Wm::Error>;
+    fn get_docking(&self) -> DockDirection;
+    fn set_title(&mut self, title: &str) -> Result<(), Wm::Error>;
+    fn set_z_index(&mut self, z_index: ZIndex) -> Result<(), Wm::Error>;
+    /// Moves the bar onto `screen`, e.g. after its resolution changed.
+    fn set_screen(&mut self, screen: &Wm::Screen) -> Result<(), Wm::Error>;
+    /// Insets the bar along its edge.
     fn set_margin(&mut self, left: i32, right: i32) -> Result<(), Wm::Error>;
+    fn set_thickness(&mut self, thickness: u32) -> Result<(), Wm::Error>;
+    /// Sets the gap between the bar and the edge of the screen.
+    fn set_offset(&mut self, offset: u32) -> Result<(), Wm::Error>;
     fn blit(&mut self, surface: &Wm::Surface, x: i32, y: i32) -> Result<(), Wm::Error>;
+    /// Copies `rect` of `surface` to the same position on the bar.
+    fn blit_rect(&mut self, surface: &Wm::Surface, rect: Rect) -> Result<(), Wm::Error>;
+    fn get_size(&self) -> (u32, u32);
+    fn get_id(&self) -> event::BarId;
+    fn destroy(self) -> Result<(), Wm::Error>;
 }
 
 pub trait WmAdapterGetBar<'a, B: Bar>: WmAdapter<B> {
@@ -31,57 +77,504 @@ pub trait WmAdapterGetBar<'a, B: Bar>: WmAdapter<B> {
 
 pub trait WmAdapterExt<B: Bar>: WmAdapter<B> + for<'a> WmAdapterGetBar<'a, B> {}
 
-pub fn run<B: Bar, Wm: WmAdapterExt<B>>() -> Result<(), RunnerError<Wm::Error>> {
-    let mut bar = B::new();
-    let builder = bar.get_bar_builder();
-    let mut wm = Wm::new(&builder)?;
-    let mut bars = Vec::with_capacity(wm.get_screen_count());
-    for i in 0..bars.capacity() {
-        let screen = wm
-            .get_screen(i)
-            .ok_or_else(|| RunnerError::Custom(format!("failed to query screen {}", i)))?;
-        fn create_bar<'a, B: Bar, Wm: WmAdapterExt<B>>(
-            bar: &B,
-            wm: &'a Wm,
-            builder: &crate::config::BarBuilder,
-            screen: &Wm::Screen,
-        ) -> Result<<Wm as WmAdapterGetBar<'a, B>>::AdapterBar, Wm::Error> {
-            <Wm as WmAdapterGetBar<'a, B>>::AdapterBar::new(bar, wm, builder, screen)
+fn query_screens<B: Bar, Wm: WmAdapter<B>>(
+    wm: &Wm,
+) -> Result<Vec<Wm::Screen>, RunnerError<Wm::Error>> {
+    (0..wm.get_screen_count())
+        .map(|i| {
+            wm.get_screen(i)
+                .ok_or_else(|| RunnerError::Custom(format!("failed to query screen {}", i)))
+        })
+        .collect()
+}
+
+type ResolvedInstances<S> = Vec<(S, BarBuilder)>;
+
+/// Resolves the bar instances to their screens and stacks bars sharing the
+/// edge of a screen by adjusting their offsets, unless the window manager
+/// does that already.
+fn bar_instances<B: Bar, Wm: WmAdapter<B>>(
+    bar: &B,
+    wm: &Wm,
+) -> Result<ResolvedInstances<Wm::Screen>, RunnerError<Wm::Error>> {
+    let screens = query_screens(wm)?;
+    let mut edges: Vec<(usize, DockDirection, u32)> = Vec::new();
+    bar.get_bar_instances(&screens)
+        .into_iter()
+        .map(|BarInstance { screen: n, builder }| {
+            let screen = screens.get(n).cloned().ok_or_else(|| {
+                RunnerError::Custom(format!("selected screen {} does not exist", n))
+            })?;
+            if Wm::STACKS_EXCLUSIVE_ZONES {
+                return Ok((screen, builder));
+            }
+            let dir = *builder.get_docking();
+            let offset = match edges.iter_mut().find(|(s, d, _)| *s == n && *d == dir) {
+                Some((_, _, end)) => {
+                    let offset = *end + builder.get_offset();
+                    *end = offset + builder.get_width();
+                    offset
+                }
+                None => {
+                    let offset = *builder.get_offset();
+                    edges.push((n, dir, offset + builder.get_width()));
+                    offset
+                }
+            };
+            Ok((screen, builder.offset(offset)))
+        })
+        .collect()
+}
+
+/// Whether `a` and `b` describe the same monitor, possibly with a different
+/// configuration.
+fn is_same_screen<S: WmScreen>(a: &S, b: &S) -> bool {
+    match (a.name(), b.name()) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.position() == b.position(),
+    }
+}
+
+fn create_bar<'a, B: Bar, Wm: WmAdapterExt<B>>(
+    bar: &B,
+    wm: &'a Wm,
+    builder: &BarBuilder,
+    screen: &Wm::Screen,
+) -> Result<<Wm as WmAdapterGetBar<'a, B>>::AdapterBar, Wm::Error> {
+    <Wm as WmAdapterGetBar<'a, B>>::AdapterBar::new(bar, wm, builder, screen)
+}
+
+struct RunningBar<S, A> {
+    id: event::BarId,
+    screen: S,
+    builder: BarBuilder,
+    bar: A,
+    damage: Rect,
+    redraw: bool,
+}
+
+type RunningAdapterBar<'a, B, Wm> =
+    RunningBar<<Wm as WmAdapter<B>>::Screen, <Wm as WmAdapterGetBar<'a, B>>::AdapterBar>;
+
+fn find_bar<S, A>(
+    bars: &mut [RunningBar<S, A>],
+    id: event::BarId,
+) -> Option<&mut RunningBar<S, A>> {
+    bars.iter_mut().find(|b| b.id == id)
+}
+
+fn start_bar<'a, B: Bar, Wm: WmAdapterExt<B>>(
+    bar: &mut B,
+    wm: &'a Wm,
+    builder: BarBuilder,
+    screen: Wm::Screen,
+) -> Result<RunningAdapterBar<'a, B, Wm>, Wm::Error> {
+    let mut adapter_bar = create_bar(bar, wm, &builder, &screen)?;
+    bar.on_bar_start::<Wm>(&mut adapter_bar);
+    let (w, h) = adapter_bar.get_size();
+    Ok(RunningBar {
+        id: adapter_bar.get_id(),
+        screen,
+        builder,
+        bar: adapter_bar,
+        damage: Rect::new(0, 0, w, h),
+        redraw: false,
+    })
+}
+
+/// The adapter-independent state of the run loop, shared by `run` and
+/// `run_async`.
+pub(crate) struct Runner<'w, B: Bar, Wm: WmAdapterExt<B>> {
+    bar: B,
+    wm: &'w Wm,
+    clicks: event::ClickTracker,
+    timers: Timers,
+    bars: Vec<RunningAdapterBar<'w, B, Wm>>,
+}
+
+impl<'w, B: Bar, Wm: WmAdapterExt<B>> Runner<'w, B, Wm> {
+    pub(crate) fn new(
+        mut bar: B,
+        wm: &'w Wm,
+        builder: &BarBuilder,
+    ) -> Result<Self, RunnerError<Wm::Error>> {
+        let mut timers = Timers::new();
+        bar.on_start(&mut timers);
+        let mut bars = Vec::new();
+        for (screen, builder) in bar_instances(&bar, wm)? {
+            bars.push(start_bar(&mut bar, wm, builder, screen)?);
         }
-        bars.push(create_bar(&bar, &wm, &builder, screen)?);
+        Ok(Self {
+            bar,
+            wm,
+            clicks: event::ClickTracker::new(*builder.get_double_click_interval()),
+            timers,
+            bars,
+        })
     }
-    loop {
-        let ev = wm.await_event()?;
+
+    fn redraw_all(&mut self) {
+        for b in self.bars.iter_mut() {
+            b.redraw = true;
+        }
+    }
+
+    /// Fires the timers that are due.
+    pub(crate) fn fire_timers(&mut self) {
+        for id in self.timers.take_expired(Instant::now()) {
+            if self.bar.on_timer(&mut self.timers, id) != Update::None {
+                self.redraw_all();
+            }
+        }
+    }
+
+    /// Polls the asynchronous work of the bar. Returns `Poll::Ready` if it
+    /// made progress.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub(crate) fn poll_bar(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let update = core::task::ready!(self.bar.poll(cx));
+        if update != Update::None {
+            self.redraw_all();
+        }
+        Poll::Ready(())
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.timers.next_deadline()
+    }
+
+    /// Draws the bars that were damaged since the last frame. Returns
+    /// whether anything was drawn.
+    pub(crate) fn draw(&mut self) -> bool {
+        let mut drawn = false;
+        for b in self.bars.iter_mut() {
+            let damage = core::mem::take(&mut b.damage);
+            if core::mem::take(&mut b.redraw) || !damage.is_empty() {
+                self.bar.on_draw::<Wm>(&mut b.bar, damage);
+                drawn = true;
+            }
+        }
+        drawn
+    }
+
+    /// Handles an event. Returns `false` once the bar should quit.
+    pub(crate) fn handle(&mut self, ev: event::Event) -> Result<bool, RunnerError<Wm::Error>> {
+        let bar = &mut self.bar;
+        let bars = &mut self.bars;
         match ev {
-            event::Event::MouseUp(ev) | event::Event::MouseDown(ev) => {
-                bar.on_click::<Wm>(bars.get_mut(0).unwrap(), ev);
+            event::Event::Quit => return Ok(false),
+            event::Event::ScreensChanged => self.screens_changed()?,
+            event::Event::Expose(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    target.damage = target.damage.union(&ev.rect);
+                }
+            }
+            event::Event::Resize(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    target.damage = Rect::new(0, 0, ev.width, ev.height);
+                    bar.on_resize::<Wm>(&mut target.bar, ev);
+                }
+            }
+            event::Event::MouseDown(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    let double = self.clicks.press(&ev);
+                    bar.on_click::<Wm>(&mut target.bar, ev.clone());
+                    if double {
+                        bar.on_double_click::<Wm>(&mut target.bar, ev);
+                    }
+                }
+            }
+            event::Event::MouseUp(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    bar.on_click::<Wm>(&mut target.bar, ev);
+                }
+            }
+            event::Event::Scroll(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    bar.on_scroll::<Wm>(&mut target.bar, ev);
+                }
+            }
+            event::Event::MouseMove(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    bar.on_mouse_move::<Wm>(&mut target.bar, ev);
+                }
+            }
+            event::Event::PointerEnter(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    bar.on_pointer_enter::<Wm>(&mut target.bar, ev);
+                }
+            }
+            event::Event::PointerLeave(ev) => {
+                if let Some(target) = find_bar(bars, ev.bar) {
+                    bar.on_pointer_leave::<Wm>(&mut target.bar, ev);
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Moves, starts and stops bars to match the bar instances on the new
+    /// screen configuration.
+    fn screens_changed(&mut self) -> Result<(), RunnerError<Wm::Error>> {
+        let instances = bar_instances(&self.bar, self.wm)?;
+        let mut old_bars = core::mem::take(&mut self.bars);
+        if let Err(e) = self.reuse_bars(instances, &mut old_bars) {
+            // keep the unmatched bars, so `quit` still destroys them
+            self.bars.append(&mut old_bars);
+            return Err(e);
+        }
+        let mut result = Ok(());
+        for mut running in old_bars {
+            self.bar.on_bar_stop::<Wm>(&mut running.bar);
+            result = result.and(running.bar.destroy().map_err(RunnerError::from));
+        }
+        result
+    }
+
+    /// Moves the bars of `old_bars` that match an instance to `self.bars`
+    /// and starts the instances that don't have a bar yet.
+    fn reuse_bars(
+        &mut self,
+        instances: ResolvedInstances<Wm::Screen>,
+        old_bars: &mut Vec<RunningAdapterBar<'w, B, Wm>>,
+    ) -> Result<(), RunnerError<Wm::Error>> {
+        for (screen, builder) in instances {
+            let old = old_bars
+                .iter()
+                .position(|b| is_same_screen(&b.screen, &screen) && b.builder == builder);
+            let i = match old {
+                Some(i) => i,
+                None => {
+                    let running = start_bar(&mut self.bar, self.wm, builder, screen)?;
+                    self.bars.push(running);
+                    continue;
+                }
+            };
+            let mut running = old_bars.swap_remove(i);
+            let moved = if running.screen != screen {
+                running.bar.set_screen(&screen)
+            } else {
+                Ok(())
+            };
+            if moved.is_ok() && running.screen != screen {
+                self.bar.on_bar_moved::<Wm>(&mut running.bar);
+                running.screen = screen;
+            }
+            self.bars.push(running);
+            moved?;
+        }
+        Ok(())
+    }
+
+    /// Destroys all bar windows after an error, ignoring further errors.
+    pub(crate) fn abort(self) {
+        for running in self.bars {
+            let _ = running.bar.destroy();
+        }
+    }
+
+    /// Notifies the bar and destroys all bar windows. Returns the bar.
+    pub(crate) fn quit(mut self) -> Result<B, RunnerError<Wm::Error>> {
+        self.bar.on_quit();
+        for running in self.bars {
+            running.bar.destroy()?;
+        }
+        Ok(self.bar)
+    }
+}
+
+pub(crate) fn quit_signals<B: Bar, E: std::error::Error>(
+    bar: &B,
+) -> Result<Option<crate::sys::QuitSignals>, RunnerError<E>> {
+    if bar.get_event_types() & event::QUIT == 0 {
+        return Ok(None);
+    }
+    crate::sys::QuitSignals::new()
+        .map(Some)
+        .map_err(|e| RunnerError::Custom(format!("failed to install signal handlers ({})", e)))
+}
+
+pub fn run<B: Bar, Wm: WmAdapterExt<B>>() -> Result<(), RunnerError<Wm::Error>> {
+    run_bar::<B, Wm>(B::new())
+}
+
+/// Like `run`, but for a bar created by the caller. Bars that pick different
+/// displays through `BarBuilder::display` can run on their own threads of
+/// the same process.
+pub fn run_bar<B: Bar, Wm: WmAdapterExt<B>>(bar: B) -> Result<(), RunnerError<Wm::Error>> {
+    let builder = bar.get_bar_builder();
+    let wm = Wm::new(&builder)?;
+    let signals = quit_signals(&bar)?;
+    let runner = Runner::new(bar, &wm, &builder)?;
+    run_loop(runner, &wm, signals).map(drop)
+}
+
+/// Runs `bar` on an adapter that was set up by the caller until it quits and
+/// returns the bar, e.g. to inspect it after running on a
+/// `headless::HeadlessAdapter`. Signals are not handled.
+pub fn run_with<B: Bar, Wm: WmAdapterExt<B>>(bar: B, wm: &Wm) -> Result<B, RunnerError<Wm::Error>> {
+    let builder = bar.get_bar_builder();
+    let runner = Runner::new(bar, wm, &builder)?;
+    run_loop(runner, wm, None)
+}
+
+fn run_loop<B: Bar, Wm: WmAdapterExt<B>>(
+    mut runner: Runner<'_, B, Wm>,
+    wm: &Wm,
+    mut signals: Option<crate::sys::QuitSignals>,
+) -> Result<B, RunnerError<Wm::Error>> {
+    loop {
+        runner.fire_timers();
+        let ev = match wm.poll_event()? {
+            Poll::Ready(ev) => ev,
+            Poll::Pending => {
+                // all queued events are handled, so draw the frame before
+                // going to sleep
+                if runner.draw() {
+                    continue;
+                }
+                let mut fds = vec![wm.get_fd()];
+                fds.extend(signals.as_ref().map(|s| s.get_fd()));
+                let timeout = runner
+                    .next_deadline()
+                    .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+                crate::sys::wait_readable(&fds, timeout).map_err(|e| {
+                    RunnerError::Custom(format!("failed to wait for events ({})", e))
+                })?;
+                if signals.as_mut().is_some_and(|s| s.take_pending()) {
+                    event::Event::Quit
+                } else {
+                    continue;
+                }
+            }
+        };
+        match runner.handle(ev) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                // don't leave bar windows and their struts behind
+                runner.abort();
+                return Err(e);
             }
         }
     }
+    drop(signals);
+    runner.quit()
 }
 
 pub trait Bar: Sized + 'static {
     fn new() -> Self;
+    /// Returns the indices of the screens to show a bar on. It is called
+    /// again whenever the screen configuration changes.
     fn select_screens<S: WmScreen>(&self, screens: &[S]) -> Vec<usize> {
         (0..screens.len()).collect()
     }
+    /// Returns the builder used to connect to the window manager and, unless
+    /// `get_bar_instances` is overridden, to create every bar.
     fn get_bar_builder(&self) -> BarBuilder {
         BarBuilder::default()
     }
+    /// Returns the bars to create. Defaults to one bar configured by
+    /// `get_bar_builder` on each screen picked by `select_screens`.
+    fn get_bar_instances<S: WmScreen>(&self, screens: &[S]) -> Vec<BarInstance> {
+        let builder = self.get_bar_builder();
+        let mut selected = self.select_screens(screens);
+        selected.sort_unstable();
+        selected.dedup();
+        selected
+            .into_iter()
+            .map(|screen| BarInstance::new(screen, builder.clone()))
+            .collect()
+    }
     fn get_event_types(&self) -> event::EventTypes {
         0
     }
+    /// Called once before any bar is created, e.g. to set up timers.
+    fn on_start(&mut self, _timers: &mut Timers) {}
+    /// Called when the timer `id` fired. Returning anything but
+    /// `Update::None` makes the runner call `on_draw` for every bar.
+    fn on_timer(&mut self, _timers: &mut Timers, _id: TimerId) -> Update {
+        Update::None
+    }
+    /// Drives asynchronous work of the bar, e.g. futures or channels its
+    /// modules wait on, with the waker of the task running the bar.
+    ///
+    /// Only `stream::run_async` calls this, whenever it is about to wait and
+    /// again once the waker is woken. Return `Poll::Pending` once nothing is
+    /// left to do; anything but `Update::None` makes the runner call
+    /// `on_draw` for every bar.
+    fn poll(&mut self, _cx: &mut Context<'_>) -> Poll<Update> {
+        Poll::Pending
+    }
     fn on_bar_start<'a, Wm: WmAdapterExt<Self>>(
         &mut self,
         _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
     ) {
     }
+    /// Called before the bar is destroyed because its screen disappeared.
+    fn on_bar_stop<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+    ) {
+    }
+    /// Called after the bar was moved or resized because its screen changed.
+    fn on_bar_moved<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+    ) {
+    }
+    /// Called when the bar's window changed its size, e.g. after
+    /// `WmAdapterBar::set_docking`.
+    fn on_resize<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::ResizeEvent,
+    ) {
+    }
+    /// Called once per frame for every bar with the union of the areas that
+    /// have to be repainted since the last call.
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _damage: Rect,
+    ) {
+    }
     fn on_click<'a, Wm: WmAdapterExt<Self>>(
         &mut self,
         _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
         _event: event::ClickEvent,
     ) {
     }
+    fn on_double_click<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::ClickEvent,
+    ) {
+    }
+    fn on_mouse_move<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::MotionEvent,
+    ) {
+    }
+    fn on_pointer_enter<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::MotionEvent,
+    ) {
+    }
+    fn on_pointer_leave<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::MotionEvent,
+    ) {
+    }
+    fn on_scroll<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _event: event::ScrollEvent,
+    ) {
+    }
     fn on_quit(&mut self) {}
 }
 
@@ -93,3 +586,8 @@ pub fn run_x11<B: Bar>() -> Result<(), RunnerError<crate::x11::X11RustAdapterErr
 pub fn run_x11_xcb<B: Bar>() -> Result<(), RunnerError<crate::x11::X11XcbAdapterError<B>>> {
     run::<B, crate::x11::X11XcbAdapter<B>>()
 }
+
+#[cfg(feature = "wm-wayland")]
+pub fn run_wayland<B: Bar>() -> Result<(), RunnerError<crate::wayland::WaylandAdapterError<B>>> {
+    run::<B, crate::wayland::WaylandAdapter<B>>()
+}
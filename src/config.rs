@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DockDirection {
     Top,
     Bottom,
@@ -8,34 +8,100 @@ pub enum DockDirection {
     Right,
 }
 
-#[derive(Debug, Clone, Copy)]
+/// Where a docked bar goes on its screen and the space it reserves, shared
+/// by all adapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DockGeometry {
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    /// The reserved space in the layout of `_NET_WM_STRUT_PARTIAL`: the
+    /// left, right, top and bottom widths, followed by the start and end of
+    /// each along its edge.
+    pub strut: [i32; 12],
+}
+
+impl DockGeometry {
+    /// Places a bar of `thickness` at `offset` from the `dir` edge of the
+    /// screen at `screen_pos`, inset by `margin` along that edge.
+    ///
+    /// Struts are measured from the edges of the root window of
+    /// `root_size`, so bars on inner screens reserve the space of the screens
+    /// behind them, and cover the offset towards the edge.
+    pub fn new(
+        dir: DockDirection,
+        (x, y): (i32, i32),
+        (sw, sh): (u32, u32),
+        (rw, rh): (u32, u32),
+        thickness: u32,
+        offset: u32,
+        (left, right): (i32, i32),
+    ) -> Self {
+        let (sw, sh, rw, rh) = (sw as i32, sh as i32, rw as i32, rh as i32);
+        let (width, offset) = (thickness as i32, offset as i32);
+        let length = |len: i32| (len - left - right).max(1) as u32;
+        let position = match dir {
+            DockDirection::Top => (x + left, y + offset),
+            DockDirection::Bottom => (x + left, y + sh - width - offset),
+            DockDirection::Left => (x + offset, y + left),
+            DockDirection::Right => (x + sw - width - offset, y + left),
+        };
+        let size = match dir {
+            DockDirection::Top | DockDirection::Bottom => (length(sw), thickness),
+            DockDirection::Left | DockDirection::Right => (thickness, length(sh)),
+        };
+        let width = width + offset;
+        #[allow(clippy::deprecated_cfg_attr)]
+        #[cfg_attr(rustfmt, rustfmt_skip)]
+        let strut = match dir {
+            DockDirection::Bottom => [0, 0, 0, rh - y - sh + width, 0, 0, 0, 0, 0, 0, x + left, x + sw - right - 1],
+            DockDirection::Top => [0, 0, y + width, 0, 0, 0, 0, 0, x + left, x + sw - right - 1, 0, 0],
+            DockDirection::Right => [0, rw - x - sw + width, 0, 0, 0, 0, y + left, y + sh - right - 1, 0, 0, 0, 0],
+            DockDirection::Left => [x + width, 0, 0, 0, y + left, y + sh - right - 1, 0, 0, 0, 0, 0, 0],
+        };
+        Self {
+            position,
+            size,
+            strut,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ZIndex {
     AboveEverything,
     Normal,
     BelowEverything,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BarBuilder {
     title: Cow<'static, str>,
+    class: Cow<'static, str>,
+    display: Option<Cow<'static, str>>,
     docking: DockDirection,
     margin_left: i32,
     margin_right: i32,
     z_index: ZIndex,
     transparency: bool,
     width: u32,
+    offset: u32,
+    double_click_interval: u32,
 }
 
 impl Default for BarBuilder {
     fn default() -> Self {
         Self {
             title: Cow::Borrowed(env!("CARGO_PKG_NAME")),
+            class: Cow::Borrowed(env!("CARGO_PKG_NAME")),
+            display: None,
             docking: DockDirection::Bottom,
             margin_left: 0,
             margin_right: 0,
             z_index: ZIndex::BelowEverything,
             transparency: false,
             width: 20,
+            offset: 0,
+            double_click_interval: 400,
         }
     }
 }
@@ -63,6 +129,40 @@ impl BarBuilder {
         self
     }
 
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn class_owned(mut self, class: String) -> Self {
+        self.class = Cow::Owned(class);
+        self
+    }
+
+    pub fn class(mut self, class: &'static str) -> Self {
+        self.class = Cow::Borrowed(class);
+        self
+    }
+
+    pub fn get_class(&self) -> &str {
+        &self.class
+    }
+
+    /// Sets the display to connect to, e.g. `":1"` on X11. Defaults to the
+    /// one named by the environment, i.e. `$DISPLAY`.
+    pub fn display(mut self, display: &'static str) -> Self {
+        self.display = Some(Cow::Borrowed(display));
+        self
+    }
+
+    pub fn display_owned(mut self, display: String) -> Self {
+        self.display = Some(Cow::Owned(display));
+        self
+    }
+
+    pub fn get_display(&self) -> Option<&str> {
+        self.display.as_deref()
+    }
+
     pub fn margin(mut self, left: i32, right: i32) -> Self {
         self.margin_left = left;
         self.margin_right = right;
@@ -75,4 +175,83 @@ impl BarBuilder {
     g_s_etter! {get_z_index, z_index, ZIndex}
     g_s_etter! {get_transparency, transparency, bool}
     g_s_etter! {get_width, width, u32}
+    g_s_etter! {get_offset, offset, u32}
+    g_s_etter! {get_double_click_interval, double_click_interval, u32}
+}
+
+/// A bar placed on the screen with index `screen`.
+///
+/// Bars docked to the same edge of a screen are stacked in the order they
+/// are listed, `offset` then being the gap to the previous bar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BarInstance {
+    pub screen: usize,
+    pub builder: BarBuilder,
+}
+
+impl BarInstance {
+    pub fn new(screen: usize, builder: BarBuilder) -> Self {
+        Self { screen, builder }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dock_geometry_per_edge() {
+        let geometry =
+            |dir| DockGeometry::new(dir, (0, 0), (800, 600), (800, 600), 20, 5, (10, 30));
+        let top = geometry(DockDirection::Top);
+        assert_eq!((top.position, top.size), ((10, 5), (760, 20)));
+        assert_eq!(top.strut, [0, 0, 25, 0, 0, 0, 0, 0, 10, 769, 0, 0]);
+        let bottom = geometry(DockDirection::Bottom);
+        assert_eq!((bottom.position, bottom.size), ((10, 575), (760, 20)));
+        assert_eq!(bottom.strut, [0, 0, 0, 25, 0, 0, 0, 0, 0, 0, 10, 769]);
+        let left = geometry(DockDirection::Left);
+        assert_eq!((left.position, left.size), ((5, 10), (20, 560)));
+        assert_eq!(left.strut, [25, 0, 0, 0, 10, 569, 0, 0, 0, 0, 0, 0]);
+        let right = geometry(DockDirection::Right);
+        assert_eq!((right.position, right.size), ((775, 10), (20, 560)));
+        assert_eq!(right.strut, [0, 25, 0, 0, 0, 0, 10, 569, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn struts_cover_outer_screens() {
+        // the left of two side by side screens, the right one being taller
+        let root = (1920 + 1280, 1200);
+        let bottom = DockGeometry::new(
+            DockDirection::Bottom,
+            (0, 0),
+            (1920, 1080),
+            root,
+            30,
+            0,
+            (0, 0),
+        );
+        assert_eq!(bottom.strut[3], 1200 - 1080 + 30);
+        assert_eq!(bottom.strut[10..], [0, 1919]);
+        let right = DockGeometry::new(
+            DockDirection::Right,
+            (0, 0),
+            (1920, 1080),
+            root,
+            30,
+            0,
+            (0, 0),
+        );
+        assert_eq!(right.strut[1], 1280 + 30);
+        // margins that eat the whole screen leave a single pixel
+        let tiny = DockGeometry::new(
+            DockDirection::Top,
+            (0, 0),
+            (100, 100),
+            (100, 100),
+            10,
+            0,
+            (60, 60),
+        );
+        assert_eq!(tiny.size, (1, 10));
+    }
 }
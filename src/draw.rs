@@ -0,0 +1,581 @@
+use crate::bar::WmSurface;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const TRANSPARENT: Color = Color::rgba(0, 0, 0, 0);
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(0xff, 0xff, 0xff);
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self::rgba(r, g, b, 0xff)
+    }
+
+    /// Creates a color from a straight (not premultiplied) `0xAARRGGBB` value.
+    pub const fn from_argb(argb: u32) -> Self {
+        Self::rgba(
+            (argb >> 16) as u8,
+            (argb >> 8) as u8,
+            argb as u8,
+            (argb >> 24) as u8,
+        )
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Self::rgba(self.r, self.g, self.b, a)
+    }
+
+    /// Returns the color as a premultiplied `0xAARRGGBB` pixel.
+    pub fn premultiplied(self) -> u32 {
+        let a = self.a as u32;
+        (a << 24)
+            | (mul_255(self.r as u32, a) << 16)
+            | (mul_255(self.g as u32, a) << 8)
+            | mul_255(self.b as u32, a)
+    }
+
+    /// Creates a color from a premultiplied `0xAARRGGBB` pixel.
+    pub fn from_premultiplied(argb: u32) -> Self {
+        let a = argb >> 24;
+        if a == 0 {
+            return Self::TRANSPARENT;
+        }
+        let f = |c: u32| ((c * 255 + a / 2) / a).min(255) as u8;
+        Self::rgba(
+            f((argb >> 16) & 0xff),
+            f((argb >> 8) & 0xff),
+            f(argb & 0xff),
+            a as u8,
+        )
+    }
+
+    fn lerp(self, other: Color, t: f32) -> Color {
+        let f = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Color::rgba(
+            f(self.r, other.r),
+            f(self.g, other.g),
+            f(self.b, other.b),
+            f(self.a, other.a),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Hash)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub const fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn right(&self) -> i32 {
+        self.x + self.width as i32
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height as i32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.right() && y < self.bottom()
+    }
+
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (r, b) = (
+            self.right().min(other.right()),
+            self.bottom().min(other.bottom()),
+        );
+        Rect::new(x, y, (r - x).max(0) as u32, (b - y).max(0) as u32)
+    }
+
+    pub fn union(&self, other: &Rect) -> Rect {
+        if self.is_empty() {
+            return *other;
+        } else if other.is_empty() {
+            return *self;
+        }
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let (r, b) = (
+            self.right().max(other.right()),
+            self.bottom().max(other.bottom()),
+        );
+        Rect::new(x, y, (r - x) as u32, (b - y) as u32)
+    }
+
+    pub fn translate(&self, dx: i32, dy: i32) -> Rect {
+        Rect::new(self.x + dx, self.y + dy, self.width, self.height)
+    }
+
+    pub fn inset(&self, amount: i32) -> Rect {
+        let w = (self.width as i32 - 2 * amount).max(0) as u32;
+        let h = (self.height as i32 - 2 * amount).max(0) as u32;
+        Rect::new(self.x + amount, self.y + amount, w, h)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LinearGradient {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+    stops: Vec<(f32, Color)>,
+}
+
+impl LinearGradient {
+    pub fn new(from: (f32, f32), to: (f32, f32), start: Color, end: Color) -> Self {
+        Self {
+            from,
+            to,
+            stops: vec![(0.0, start), (1.0, end)],
+        }
+    }
+
+    pub fn horizontal(rect: Rect, start: Color, end: Color) -> Self {
+        let y = rect.y as f32;
+        Self::new((rect.x as f32, y), (rect.right() as f32, y), start, end)
+    }
+
+    pub fn vertical(rect: Rect, start: Color, end: Color) -> Self {
+        let x = rect.x as f32;
+        Self::new((x, rect.y as f32), (x, rect.bottom() as f32), start, end)
+    }
+
+    pub fn stop(mut self, offset: f32, color: Color) -> Self {
+        let offset = offset.clamp(0.0, 1.0);
+        let i = self.stops.partition_point(|(o, _)| *o <= offset);
+        self.stops.insert(i, (offset, color));
+        self
+    }
+
+    fn color_at(&self, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let i = self.stops.partition_point(|(o, _)| *o <= t);
+        match (i.checked_sub(1).map(|i| self.stops[i]), self.stops.get(i)) {
+            (Some((o0, c0)), Some(&(o1, c1))) if o1 > o0 => c0.lerp(c1, (t - o0) / (o1 - o0)),
+            (Some((_, c)), _) | (None, Some(&(_, c))) => c,
+            (None, None) => Color::TRANSPARENT,
+        }
+    }
+}
+
+fn mul_255(a: u32, b: u32) -> u32 {
+    let v = a * b + 128;
+    (v + (v >> 8)) >> 8
+}
+
+fn scale_pixel(px: u32, coverage: u32) -> u32 {
+    if coverage >= 255 {
+        return px;
+    }
+    let rb = (px & 0x00ff00ff) * coverage + 0x00800080;
+    let rb = ((rb + ((rb >> 8) & 0x00ff00ff)) >> 8) & 0x00ff00ff;
+    let ag = ((px >> 8) & 0x00ff00ff) * coverage + 0x00800080;
+    let ag = (ag + ((ag >> 8) & 0x00ff00ff)) & 0xff00ff00;
+    rb | ag
+}
+
+/// Composites the premultiplied pixel `src` over `dst`.
+pub fn blend_over(dst: u32, src: u32) -> u32 {
+    let inv = 255 - (src >> 24);
+    if inv == 0 {
+        src
+    } else {
+        src + scale_pixel(dst, inv)
+    }
+}
+
+/// Draws onto a surface, clipped to a rectangle and translated so that the
+/// top-left corner of that rectangle is the origin.
+pub struct Painter<'s, S: WmSurface> {
+    surface: &'s mut S,
+    clip: Rect,
+    origin: (i32, i32),
+}
+
+impl<'s, S: WmSurface> Painter<'s, S> {
+    pub fn new(surface: &'s mut S) -> Self {
+        let (w, h) = surface.dimensions();
+        Self {
+            surface,
+            clip: Rect::new(0, 0, w, h),
+            origin: (0, 0),
+        }
+    }
+
+    /// Returns a painter restricted to `rect`, whose origin is `rect`'s corner.
+    pub fn sub(&mut self, rect: Rect) -> Painter<'_, S> {
+        let abs = rect.translate(self.origin.0, self.origin.1);
+        Painter {
+            clip: self.clip.intersect(&abs),
+            origin: (abs.x, abs.y),
+            surface: self.surface,
+        }
+    }
+
+    /// The area that can be painted, in local coordinates.
+    pub fn bounds(&self) -> Rect {
+        self.clip.translate(-self.origin.0, -self.origin.1)
+    }
+
+    fn clipped(&self, rect: Rect) -> Rect {
+        rect.translate(self.origin.0, self.origin.1)
+            .intersect(&self.clip)
+    }
+
+    fn span<F: FnMut(i32, &mut u32)>(&mut self, rect: Rect, mut f: F) {
+        let rect = self.clipped(rect);
+        if rect.is_empty() {
+            return;
+        }
+        let stride = self.surface.dimensions().0 as usize;
+        let pixels = self.surface.pixels_mut();
+        for y in rect.y..rect.bottom() {
+            let row = y as usize * stride;
+            let line = &mut pixels[row + rect.x as usize..row + rect.right() as usize];
+            for (x, px) in (rect.x..).zip(line.iter_mut()) {
+                f(x - self.origin.0, px);
+            }
+        }
+    }
+
+    /// Blends `color` into a single pixel with an extra coverage in `0..=255`.
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: Color, coverage: u8) {
+        let (ax, ay) = (x + self.origin.0, y + self.origin.1);
+        if !self.clip.contains(ax, ay) {
+            return;
+        }
+        let stride = self.surface.dimensions().0 as usize;
+        let px = &mut self.surface.pixels_mut()[ay as usize * stride + ax as usize];
+        *px = blend_over(*px, scale_pixel(color.premultiplied(), coverage as u32));
+    }
+
+    /// Overwrites all pixels of the painted area, including their alpha.
+    pub fn clear(&mut self, color: Color) {
+        let src = color.premultiplied();
+        let bounds = self.bounds();
+        self.span(bounds, |_, px| *px = src);
+    }
+
+    pub fn fill_rect(&mut self, rect: Rect, color: Color) {
+        let src = color.premultiplied();
+        self.span(rect, |_, px| *px = blend_over(*px, src));
+    }
+
+    pub fn stroke_rect(&mut self, rect: Rect, thickness: u32, color: Color) {
+        let t = thickness.min(rect.height / 2 + rect.height % 2);
+        let side = rect.height.saturating_sub(2 * t);
+        self.fill_rect(Rect::new(rect.x, rect.y, rect.width, t), color);
+        self.fill_rect(
+            Rect::new(rect.x, rect.bottom() - t as i32, rect.width, t),
+            color,
+        );
+        let tw = thickness.min(rect.width);
+        let y = rect.y + t as i32;
+        self.fill_rect(Rect::new(rect.x, y, tw, side), color);
+        self.fill_rect(Rect::new(rect.right() - tw as i32, y, tw, side), color);
+    }
+
+    pub fn fill_rounded_rect(&mut self, rect: Rect, radius: u32, color: Color) {
+        let radius = radius.min(rect.width / 2).min(rect.height / 2);
+        if radius == 0 {
+            return self.fill_rect(rect, color);
+        }
+        let src = color.premultiplied();
+        let r = radius as f32;
+        let (x0, y0) = (rect.x as f32 + r, rect.y as f32 + r);
+        let (x1, y1) = (rect.right() as f32 - r, rect.bottom() as f32 - r);
+        let dist = |v: f32, lo: f32, hi: f32| (lo - v).max(v - hi).max(0.0);
+        let clip = self.clipped(rect);
+        let oy = self.origin.1;
+        for y in clip.y - oy..clip.bottom() - oy {
+            let dy = dist(y as f32 + 0.5, y0, y1);
+            self.span(Rect::new(rect.x, y, rect.width, 1), |x, px| {
+                let dx = dist(x as f32 + 0.5, x0, x1);
+                let coverage = if dx > 0.0 && dy > 0.0 {
+                    (r + 0.5 - (dx * dx + dy * dy).sqrt()).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                if coverage > 0.0 {
+                    let c = (coverage * 255.0).round() as u32;
+                    *px = blend_over(*px, scale_pixel(src, c));
+                }
+            });
+        }
+    }
+
+    pub fn fill_gradient(&mut self, rect: Rect, gradient: &LinearGradient) {
+        let (fx, fy) = gradient.from;
+        let (dx, dy) = (gradient.to.0 - fx, gradient.to.1 - fy);
+        let len2 = dx * dx + dy * dy;
+        let clip = self.clipped(rect);
+        let oy = self.origin.1;
+        for y in clip.y - oy..clip.bottom() - oy {
+            let py = y as f32 + 0.5 - fy;
+            self.span(Rect::new(rect.x, y, rect.width, 1), |x, px| {
+                let t = if len2 > 0.0 {
+                    ((x as f32 + 0.5 - fx) * dx + py * dy) / len2
+                } else {
+                    0.0
+                };
+                *px = blend_over(*px, gradient.color_at(t).premultiplied());
+            });
+        }
+    }
+
+    pub fn hline(&mut self, x: i32, y: i32, length: u32, thickness: u32, color: Color) {
+        self.fill_rect(Rect::new(x, y, length, thickness), color);
+    }
+
+    pub fn vline(&mut self, x: i32, y: i32, length: u32, thickness: u32, color: Color) {
+        self.fill_rect(Rect::new(x, y, thickness, length), color);
+    }
+
+    /// Draws an antialiased one pixel wide line.
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), color: Color) {
+        let ((mut x0, mut y0), (mut x1, mut y1)) = (from, to);
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        if steep {
+            core::mem::swap(&mut x0, &mut y0);
+            core::mem::swap(&mut x1, &mut y1);
+        }
+        if x0 > x1 {
+            core::mem::swap(&mut x0, &mut x1);
+            core::mem::swap(&mut y0, &mut y1);
+        }
+        let gradient = if x1 - x0 == 0.0 {
+            1.0
+        } else {
+            (y1 - y0) / (x1 - x0)
+        };
+        let mut plot = |x: i32, y: i32, c: f32| {
+            let c = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+            if steep {
+                self.blend_pixel(y, x, color, c)
+            } else {
+                self.blend_pixel(x, y, color, c)
+            }
+        };
+        let mut y = y0 + gradient * (x0.round() - x0);
+        for x in x0.round() as i32..=x1.round() as i32 {
+            let fract = y - y.floor();
+            plot(x, y.floor() as i32, 1.0 - fract);
+            plot(x, y.floor() as i32 + 1, fract);
+            y += gradient;
+        }
+    }
+
+    pub fn underline(&mut self, rect: Rect, thickness: u32, color: Color) {
+        let t = thickness.min(rect.height);
+        self.hline(rect.x, rect.bottom() - t as i32, rect.width, t, color);
+    }
+
+    pub fn overline(&mut self, rect: Rect, thickness: u32, color: Color) {
+        self.hline(
+            rect.x,
+            rect.y,
+            rect.width,
+            thickness.min(rect.height),
+            color,
+        );
+    }
+
+    /// Draws a vertical separator centered in `rect`, leaving `margin` pixels
+    /// free at the top and bottom.
+    pub fn separator(&mut self, rect: Rect, thickness: u32, margin: u32, color: Color) {
+        let x = rect.x + (rect.width.saturating_sub(thickness) / 2) as i32;
+        let length = rect.height.saturating_sub(2 * margin);
+        self.vline(x, rect.y + margin as i32, length, thickness, color);
+    }
+
+    /// Blends a coverage mask of `width` columns, e.g. a rasterized glyph,
+    /// with its top-left corner at (`x`, `y`).
+    pub fn draw_mask(&mut self, x: i32, y: i32, width: u32, mask: &[u8], color: Color) {
+        if width == 0 {
+            return;
+        }
+        let src = color.premultiplied();
+        let height = (mask.len() / width as usize) as u32;
+        let clip = self.clipped(Rect::new(x, y, width, height));
+        let oy = self.origin.1;
+        for row in clip.y - oy..clip.bottom() - oy {
+            let line = &mask[(row - y) as usize * width as usize..];
+            self.span(Rect::new(x, row, width, 1), |col, px| {
+                let c = line[(col - x) as usize] as u32;
+                if c != 0 {
+                    *px = blend_over(*px, scale_pixel(src, c));
+                }
+            });
+        }
+    }
+
+    /// Composites another premultiplied surface over this one.
+    pub fn draw_surface<T: WmSurface>(&mut self, src: &T, x: i32, y: i32) {
+        let (w, h) = src.dimensions();
+        let pixels = src.pixels();
+        let clip = self.clipped(Rect::new(x, y, w, h));
+        let oy = self.origin.1;
+        for row in clip.y - oy..clip.bottom() - oy {
+            let line = &pixels[(row - y) as usize * w as usize..];
+            self.span(Rect::new(x, row, w, 1), |col, px| {
+                *px = blend_over(*px, line[(col - x) as usize]);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::surface::Surface;
+
+    const RED: Color = Color::rgb(0xff, 0, 0);
+    const BLUE: Color = Color::rgb(0, 0, 0xff);
+
+    fn pixel(surface: &Surface, x: u32, y: u32) -> Color {
+        Color::from_premultiplied(surface.get_pixel(x, y).unwrap())
+    }
+
+    #[test]
+    fn premultiplied_round_trip() {
+        let c = Color::rgba(0xff, 0x80, 0x00, 0x80);
+        assert_eq!(c.premultiplied(), 0x80804000);
+        assert_eq!(Color::from_premultiplied(c.premultiplied()), c);
+        assert_eq!(Color::TRANSPARENT.premultiplied(), 0);
+    }
+
+    #[test]
+    fn blend_over_premultiplied() {
+        let half_red = RED.with_alpha(0x80).premultiplied();
+        assert_eq!(blend_over(BLUE.premultiplied(), half_red), 0xff80007f);
+        // blending onto nothing keeps the source as is
+        assert_eq!(blend_over(0, half_red), half_red);
+        assert_eq!(blend_over(half_red, 0), half_red);
+        assert_eq!(blend_over(half_red, BLUE.premultiplied()), 0xff0000ff);
+    }
+
+    #[test]
+    fn fill_rect_is_clipped_to_surface() {
+        let mut surface = Surface::new(4, 4);
+        Painter::new(&mut surface).fill_rect(Rect::new(-2, 2, 4, 10), RED);
+        for y in 0..4 {
+            for x in 0..4 {
+                let expected = if x < 2 && y >= 2 {
+                    RED
+                } else {
+                    Color::TRANSPARENT
+                };
+                assert_eq!(pixel(&surface, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn sub_painter_translates_and_clips() {
+        let mut surface = Surface::new(6, 6);
+        let mut painter = Painter::new(&mut surface);
+        let mut sub = painter.sub(Rect::new(2, 2, 2, 2));
+        assert_eq!(sub.bounds(), Rect::new(0, 0, 2, 2));
+        sub.fill_rect(Rect::new(-1, -1, 10, 10), RED);
+        sub.blend_pixel(2, 0, BLUE, 0xff);
+        sub.line((0.0, 3.0), (5.0, 3.0), BLUE);
+        for y in 0..6 {
+            for x in 0..6 {
+                let inside = (2..4).contains(&x) && (2..4).contains(&y);
+                let expected = if inside { RED } else { Color::TRANSPARENT };
+                assert_eq!(pixel(&surface, x, y), expected, "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn clear_overwrites_alpha() {
+        let mut surface = Surface::new(2, 1);
+        let mut painter = Painter::new(&mut surface);
+        painter.fill_rect(Rect::new(0, 0, 2, 1), RED);
+        painter.sub(Rect::new(1, 0, 1, 1)).clear(Color::TRANSPARENT);
+        assert_eq!(pixel(&surface, 0, 0), RED);
+        assert_eq!(pixel(&surface, 1, 0), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn rounded_rect_corners() {
+        let mut surface = Surface::new(10, 10);
+        Painter::new(&mut surface).fill_rounded_rect(Rect::new(0, 0, 10, 10), 4, RED);
+        // the corners are cut off, the edges between them are not
+        for &(x, y) in [(0, 0), (9, 0), (0, 9), (9, 9)].iter() {
+            assert_eq!(pixel(&surface, x, y).a, 0, "({}, {})", x, y);
+        }
+        for &(x, y) in [(5, 0), (0, 5), (9, 5), (5, 9), (5, 5)].iter() {
+            assert_eq!(pixel(&surface, x, y), RED, "({}, {})", x, y);
+        }
+        // pixels on the arc are antialiased
+        let arc = pixel(&surface, 1, 1).a;
+        assert!(arc > 0 && arc < 0xff, "alpha {}", arc);
+        assert_eq!(pixel(&surface, 1, 1).r, 0xff);
+    }
+
+    #[test]
+    fn rounded_rect_radius_is_limited() {
+        let mut a = Surface::new(8, 4);
+        let mut b = Surface::new(8, 4);
+        Painter::new(&mut a).fill_rounded_rect(Rect::new(0, 0, 8, 4), 100, RED);
+        Painter::new(&mut b).fill_rounded_rect(Rect::new(0, 0, 8, 4), 2, RED);
+        assert_eq!(a.data(), b.data());
+    }
+
+    #[test]
+    fn horizontal_gradient() {
+        let mut surface = Surface::new(5, 1);
+        let rect = Rect::new(0, 0, 5, 1);
+        let gradient = LinearGradient::horizontal(rect, RED, BLUE);
+        Painter::new(&mut surface).fill_gradient(rect, &gradient);
+        // pixels are sampled at their centers
+        assert_eq!(pixel(&surface, 0, 0), Color::rgb(0xe6, 0, 0x1a));
+        assert_eq!(pixel(&surface, 2, 0), Color::rgb(0x80, 0, 0x80));
+        assert_eq!(pixel(&surface, 4, 0), Color::rgb(0x1a, 0, 0xe6));
+    }
+
+    #[test]
+    fn gradient_stops() {
+        let gradient = LinearGradient::new((0.0, 0.0), (1.0, 0.0), RED, BLUE)
+            .stop(0.5, Color::WHITE)
+            .stop(2.0, Color::BLACK);
+        assert_eq!(gradient.color_at(-1.0), RED);
+        assert_eq!(gradient.color_at(0.5), Color::WHITE);
+        assert_eq!(gradient.color_at(0.25), Color::rgb(0xff, 0x80, 0x80));
+        // stops outside 0..=1 are clamped and the later one wins
+        assert_eq!(gradient.color_at(1.0), Color::BLACK);
+    }
+
+    #[test]
+    fn draw_mask_uses_coverage() {
+        let mut surface = Surface::new(3, 1);
+        Painter::new(&mut surface).draw_mask(0, 0, 3, &[0, 0x80, 0xff], Color::WHITE);
+        assert_eq!(surface.data(), &[0, 0x80808080, 0xffffffff]);
+    }
+}
@@ -1,16 +1,195 @@
+pub type BarId = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+    Back,
+    Forward,
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollDirection {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+pub type Modifiers = u32;
+pub const MOD_SHIFT: u32 = 1;
+pub const MOD_CTRL: u32 = 2;
+pub const MOD_ALT: u32 = 4;
+pub const MOD_SUPER: u32 = 8;
+
 #[derive(Debug, Clone)]
 pub struct ClickEvent {
+    pub bar: BarId,
+    pub x: i32,
+    pub y: i32,
+    pub root_x: i32,
+    pub root_y: i32,
+    pub button: MouseButton,
+    pub pressed: bool,
+    pub modifiers: Modifiers,
+    pub time: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScrollEvent {
+    pub bar: BarId,
     pub x: i32,
     pub y: i32,
+    pub root_x: i32,
+    pub root_y: i32,
+    pub direction: ScrollDirection,
+    pub modifiers: Modifiers,
+    pub time: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct MotionEvent {
+    pub bar: BarId,
+    pub x: i32,
+    pub y: i32,
+    pub root_x: i32,
+    pub root_y: i32,
+    pub modifiers: Modifiers,
+    pub time: u32,
+}
+
+/// A part of a bar that has to be repainted, in bar-local coordinates.
+#[derive(Debug, Clone)]
+pub struct ExposeEvent {
+    pub bar: BarId,
+    pub rect: crate::draw::Rect,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResizeEvent {
+    pub bar: BarId,
+    pub width: u32,
+    pub height: u32,
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
     MouseDown(ClickEvent),
     MouseUp(ClickEvent),
+    Scroll(ScrollEvent),
+    MouseMove(MotionEvent),
+    PointerEnter(MotionEvent),
+    PointerLeave(MotionEvent),
+    Resize(ResizeEvent),
+    Expose(ExposeEvent),
+    Quit,
+    ScreensChanged,
+}
+
+/// Detects double clicks from a stream of button presses.
+#[derive(Debug, Clone)]
+pub struct ClickTracker {
+    interval: u32,
+    distance: i32,
+    last: Option<ClickEvent>,
+}
+
+impl ClickTracker {
+    pub fn new(interval: u32) -> Self {
+        Self {
+            interval,
+            distance: 4,
+            last: None,
+        }
+    }
+
+    /// Records a press and returns whether it completes a double click.
+    pub fn press(&mut self, ev: &ClickEvent) -> bool {
+        let double = self.last.take().is_some_and(|last| {
+            last.bar == ev.bar
+                && last.button == ev.button
+                && ev.time.wrapping_sub(last.time) <= self.interval
+                && (ev.x - last.x).abs() <= self.distance
+                && (ev.y - last.y).abs() <= self.distance
+        });
+        if !double {
+            self.last = Some(ev.clone());
+        }
+        double
+    }
 }
 
 pub type EventTypes = u32;
 pub const QUIT: u32 = 1;
 pub const CLICK: u32 = 2;
 pub const MOUSE_MOVE: u32 = 4;
+pub const SCROLL: u32 = 8;
+pub const HOVER: u32 = 16;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(bar: BarId, button: MouseButton, time: u32) -> ClickEvent {
+        ClickEvent {
+            bar,
+            x: 10,
+            y: 5,
+            root_x: 10,
+            root_y: 5,
+            button,
+            pressed: true,
+            modifiers: 0,
+            time,
+        }
+    }
+
+    #[test]
+    fn double_click_within_interval() {
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1000)));
+        assert!(tracker.press(&press(1, MouseButton::Left, 1400)));
+        // the second press completed the pair, so a third one starts anew
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1500)));
+    }
+
+    #[test]
+    fn different_button() {
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1000)));
+        assert!(!tracker.press(&press(1, MouseButton::Right, 1100)));
+        assert!(tracker.press(&press(1, MouseButton::Right, 1200)));
+    }
+
+    #[test]
+    fn different_bar() {
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1000)));
+        assert!(!tracker.press(&press(2, MouseButton::Left, 1100)));
+        assert!(tracker.press(&press(2, MouseButton::Left, 1200)));
+    }
+
+    #[test]
+    fn interval_exceeded() {
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1000)));
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1401)));
+        // server time wraps around after about 49 days
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, u32::MAX - 100)));
+        assert!(tracker.press(&press(1, MouseButton::Left, 100)));
+    }
+
+    #[test]
+    fn pointer_moved_too_far() {
+        let mut tracker = ClickTracker::new(400);
+        assert!(!tracker.press(&press(1, MouseButton::Left, 1000)));
+        let moved = ClickEvent {
+            x: 15,
+            ..press(1, MouseButton::Left, 1100)
+        };
+        assert!(!tracker.press(&moved));
+    }
+}
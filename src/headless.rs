@@ -0,0 +1,419 @@
+use core::cell::{Cell, RefCell};
+use core::task::Poll;
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+
+use crate::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmScreen};
+use crate::config::{BarBuilder, DockDirection, DockGeometry, ZIndex};
+use crate::draw::Rect;
+use crate::event;
+use crate::surface::Surface;
+
+#[derive(Debug)]
+pub enum Error {
+    Custom(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Custom(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// A fake monitor of a `HeadlessAdapter`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessScreen {
+    pos: (i32, i32),
+    size: (u32, u32),
+    physical_size: Option<(f32, f32)>,
+    name: Option<String>,
+    primary: bool,
+}
+
+impl HeadlessScreen {
+    pub fn new(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self {
+            pos: (x, y),
+            size: (width, height),
+            physical_size: None,
+            name: None,
+            primary: false,
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    pub fn primary(mut self, primary: bool) -> Self {
+        self.primary = primary;
+        self
+    }
+
+    /// Sets the size in millimeters.
+    pub fn physical_size(mut self, width: f32, height: f32) -> Self {
+        self.physical_size = Some((width, height));
+        self
+    }
+}
+
+impl WmScreen for HeadlessScreen {
+    fn dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+    fn physical_dimensions(&self) -> Option<(f32, f32)> {
+        self.physical_size
+    }
+    fn position(&self) -> (i32, i32) {
+        self.pos
+    }
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// A snapshot of a bar created on a `HeadlessAdapter`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarState {
+    pub id: event::BarId,
+    pub screen: HeadlessScreen,
+    pub title: String,
+    pub class: String,
+    pub docking: DockDirection,
+    pub z_index: ZIndex,
+    pub transparent: bool,
+    /// The insets along the edge, `(left, right)`.
+    pub margin: (i32, i32),
+    pub thickness: u32,
+    pub offset: u32,
+    pub position: (i32, i32),
+    pub size: (u32, u32),
+    /// The values an X11 bar would set as `_NET_WM_STRUT_PARTIAL`.
+    pub strut: [i32; 12],
+    /// The contents of the bar as last blitted.
+    pub pixels: Surface,
+    pub blits: usize,
+    pub destroyed: bool,
+}
+
+impl BarState {
+    fn reconfigure(&mut self, root_size: (u32, u32)) {
+        let geometry = DockGeometry::new(
+            self.docking,
+            self.screen.pos,
+            self.screen.size,
+            root_size,
+            self.thickness,
+            self.offset,
+            self.margin,
+        );
+        self.position = geometry.position;
+        self.size = geometry.size;
+        self.strut = geometry.strut;
+        if self.pixels.width() != self.size.0 || self.pixels.height() != self.size.1 {
+            self.pixels.resize(self.size.0, self.size.1);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Step {
+    Event(event::Event),
+    Screens(Vec<HeadlessScreen>),
+}
+
+/// A window manager adapter that keeps its bars in memory, so bars can be
+/// tested and rendered without a display.
+///
+/// Events are taken from a script filled by `push_event` and `push_screens`.
+/// Once the script is exhausted, the adapter reports `Pending` once so the
+/// runner draws the last frame, then `Event::Quit`, unless `quit_when_idle`
+/// is disabled. Bar ids are assigned in creation order, starting at 1.
+#[derive(Debug)]
+pub struct HeadlessAdapter {
+    screens: RefCell<Vec<HeadlessScreen>>,
+    script: RefCell<VecDeque<Step>>,
+    bars: RefCell<Vec<BarState>>,
+    idle: Cell<bool>,
+    quit_when_idle: bool,
+    // a socket that is readable while the run loop shouldn't sleep
+    wake: (UnixStream, UnixStream),
+}
+
+impl HeadlessAdapter {
+    pub fn with_screens(screens: Vec<HeadlessScreen>) -> Result<Self, Error> {
+        let io_error =
+            |e: std::io::Error| Error::Custom(format!("failed to create wakeup socket ({})", e));
+        let (mut tx, rx) = UnixStream::pair().map_err(io_error)?;
+        tx.set_nonblocking(true).map_err(io_error)?;
+        rx.set_nonblocking(true).map_err(io_error)?;
+        tx.write_all(&[0]).map_err(io_error)?;
+        Ok(Self {
+            screens: RefCell::new(screens),
+            script: RefCell::new(VecDeque::new()),
+            bars: RefCell::new(Vec::new()),
+            idle: Cell::new(false),
+            quit_when_idle: true,
+            wake: (tx, rx),
+        })
+    }
+
+    /// Sets whether the adapter quits once the script is exhausted. If not,
+    /// it reports `Pending` and the run loop sleeps until the next push,
+    /// e.g. from a future running next to `stream::run_async_with`.
+    pub fn quit_when_idle(mut self, quit: bool) -> Self {
+        self.quit_when_idle = quit;
+        self
+    }
+
+    /// Appends an event to the script.
+    pub fn push_event(&self, ev: event::Event) {
+        self.script.borrow_mut().push_back(Step::Event(ev));
+        self.wake_up();
+    }
+
+    pub fn push_events<I: IntoIterator<Item = event::Event>>(&self, events: I) {
+        self.script
+            .borrow_mut()
+            .extend(events.into_iter().map(Step::Event));
+        self.wake_up();
+    }
+
+    /// Appends a change of the screen configuration to the script. It is
+    /// reported as `Event::ScreensChanged`.
+    pub fn push_screens(&self, screens: Vec<HeadlessScreen>) {
+        self.script.borrow_mut().push_back(Step::Screens(screens));
+        self.wake_up();
+    }
+
+    fn wake_up(&self) {
+        // a full socket is readable already
+        let _ = (&self.wake.0).write(&[0]);
+    }
+
+    fn drain_wakeups(&self) {
+        let mut buf = [0; 64];
+        while matches!((&self.wake.1).read(&mut buf), Ok(n) if n > 0) {}
+    }
+
+    pub fn screens(&self) -> Vec<HeadlessScreen> {
+        self.screens.borrow().clone()
+    }
+
+    /// Returns all bars created so far, including destroyed ones.
+    pub fn bars(&self) -> Vec<BarState> {
+        self.bars.borrow().clone()
+    }
+
+    pub fn bar(&self, id: event::BarId) -> Option<BarState> {
+        self.bars.borrow().iter().find(|b| b.id == id).cloned()
+    }
+
+    /// The bounding box of all screens.
+    fn root_size(&self) -> (u32, u32) {
+        self.screens.borrow().iter().fold((0, 0), |(w, h), s| {
+            let (x, y) = s.pos;
+            (
+                w.max((x + s.size.0 as i32).max(0) as u32),
+                h.max((y + s.size.1 as i32).max(0) as u32),
+            )
+        })
+    }
+
+    fn next_event(&self) -> Option<event::Event> {
+        let step = self.script.borrow_mut().pop_front()?;
+        self.idle.set(false);
+        Some(match step {
+            Step::Event(ev) => ev,
+            Step::Screens(screens) => {
+                *self.screens.borrow_mut() = screens;
+                event::Event::ScreensChanged
+            }
+        })
+    }
+}
+
+impl<'a, B: Bar> WmAdapterGetBar<'a, B> for HeadlessAdapter {
+    type AdapterBar = HeadlessBar<'a>;
+}
+
+impl<B: Bar> WmAdapterExt<B> for HeadlessAdapter {}
+
+impl<B: Bar> WmAdapter<B> for HeadlessAdapter {
+    type Error = Error;
+    type Surface = Surface;
+    type Screen = HeadlessScreen;
+
+    /// Creates an adapter with a single 1920x1080 screen.
+    fn new(_cfg: &BarBuilder) -> Result<Self, Error> {
+        Self::with_screens(vec![HeadlessScreen::new(0, 0, 1920, 1080)
+            .named("HEADLESS-1")
+            .primary(true)])
+    }
+    fn get_screen_count(&self) -> usize {
+        self.screens.borrow().len()
+    }
+    fn get_screen(&self, n: usize) -> Option<HeadlessScreen> {
+        self.screens.borrow().get(n).cloned()
+    }
+    fn await_event(&self) -> Result<event::Event, Error> {
+        Ok(self.next_event().unwrap_or(event::Event::Quit))
+    }
+    fn poll_event(&self) -> Result<Poll<event::Event>, Error> {
+        Ok(match self.next_event() {
+            Some(ev) => Poll::Ready(ev),
+            None if !self.quit_when_idle => {
+                self.drain_wakeups();
+                Poll::Pending
+            }
+            None if self.idle.replace(true) => Poll::Ready(event::Event::Quit),
+            None => Poll::Pending,
+        })
+    }
+    fn get_fd(&self) -> RawFd {
+        self.wake.1.as_raw_fd()
+    }
+}
+
+pub struct HeadlessBar<'a> {
+    wm: &'a HeadlessAdapter,
+    id: event::BarId,
+}
+
+impl<'a> HeadlessBar<'a> {
+    fn with_state<R>(&self, f: impl FnOnce(&mut BarState) -> R) -> R {
+        let mut bars = self.wm.bars.borrow_mut();
+        let state = bars
+            .iter_mut()
+            .find(|b| b.id == self.id)
+            .expect("headless bar state vanished");
+        f(state)
+    }
+
+    /// Applies a change of the geometry and queues a resize event like a
+    /// window manager would.
+    fn reconfigure(&mut self, f: impl FnOnce(&mut BarState)) {
+        let root_size = self.wm.root_size();
+        let resized = self.with_state(|state| {
+            f(state);
+            let old = state.size;
+            state.reconfigure(root_size);
+            (old != state.size).then_some(state.size)
+        });
+        if let Some((width, height)) = resized {
+            self.wm
+                .script
+                .borrow_mut()
+                .push_front(Step::Event(event::Event::Resize(event::ResizeEvent {
+                    bar: self.id,
+                    width,
+                    height,
+                })));
+        }
+    }
+
+    fn copy(&mut self, surface: &Surface, src: Rect, x: i32, y: i32) {
+        self.with_state(|state| {
+            state.pixels.copy_rect(surface, src, x, y);
+            state.blits += 1;
+        })
+    }
+}
+
+impl<'a, B: Bar> WmAdapterBar<'a, B, HeadlessAdapter> for HeadlessBar<'a> {
+    fn new(
+        _bar: &B,
+        wm: &'a HeadlessAdapter,
+        cfg: &BarBuilder,
+        screen: &HeadlessScreen,
+    ) -> Result<Self, Error> {
+        if *cfg.get_width() == 0 {
+            return Err(Error::Custom("invalid bar width (0)".to_owned()));
+        }
+        let id = wm.bars.borrow().len() as event::BarId + 1;
+        let mut state = BarState {
+            id,
+            screen: screen.clone(),
+            title: cfg.get_title().to_owned(),
+            class: cfg.get_class().to_owned(),
+            docking: *cfg.get_docking(),
+            z_index: *cfg.get_z_index(),
+            transparent: *cfg.get_transparency(),
+            margin: (*cfg.get_margin_left(), *cfg.get_margin_right()),
+            thickness: *cfg.get_width(),
+            offset: *cfg.get_offset(),
+            position: (0, 0),
+            size: (0, 0),
+            strut: [0; 12],
+            pixels: Surface::new(0, 0),
+            blits: 0,
+            destroyed: false,
+        };
+        state.reconfigure(wm.root_size());
+        wm.bars.borrow_mut().push(state);
+        Ok(Self { wm, id })
+    }
+    fn set_docking(&mut self, dir: DockDirection) -> Result<(), Error> {
+        self.reconfigure(|state| state.docking = dir);
+        Ok(())
+    }
+    fn get_docking(&self) -> DockDirection {
+        self.with_state(|state| state.docking)
+    }
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.with_state(|state| state.title = title.to_owned());
+        Ok(())
+    }
+    fn set_z_index(&mut self, z_index: ZIndex) -> Result<(), Error> {
+        self.with_state(|state| state.z_index = z_index);
+        Ok(())
+    }
+    fn set_screen(&mut self, screen: &HeadlessScreen) -> Result<(), Error> {
+        self.reconfigure(|state| state.screen = screen.clone());
+        Ok(())
+    }
+    fn set_margin(&mut self, left: i32, right: i32) -> Result<(), Error> {
+        self.reconfigure(|state| state.margin = (left, right));
+        Ok(())
+    }
+    fn set_thickness(&mut self, thickness: u32) -> Result<(), Error> {
+        if thickness == 0 {
+            return Err(Error::Custom("invalid bar width (0)".to_owned()));
+        }
+        self.reconfigure(|state| state.thickness = thickness);
+        Ok(())
+    }
+    fn set_offset(&mut self, offset: u32) -> Result<(), Error> {
+        self.reconfigure(|state| state.offset = offset);
+        Ok(())
+    }
+    fn blit(&mut self, surface: &Surface, x: i32, y: i32) -> Result<(), Error> {
+        let rect = Rect::new(0, 0, surface.width(), surface.height());
+        self.copy(surface, rect, x, y);
+        Ok(())
+    }
+    fn blit_rect(&mut self, surface: &Surface, rect: Rect) -> Result<(), Error> {
+        self.copy(surface, rect, rect.x, rect.y);
+        Ok(())
+    }
+    fn get_size(&self) -> (u32, u32) {
+        self.with_state(|state| state.size)
+    }
+    fn get_id(&self) -> event::BarId {
+        self.id
+    }
+    fn destroy(self) -> Result<(), Error> {
+        self.with_state(|state| state.destroyed = true);
+        Ok(())
+    }
+}
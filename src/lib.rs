@@ -1,13 +1,28 @@
-#![feature(array_value_iter)]
-#![feature(min_const_generics)]
-
 pub mod bar;
 pub mod config;
+pub mod draw;
 pub mod error;
 pub mod event;
+pub mod headless;
+pub mod module;
+#[cfg(feature = "snapshot")]
+pub mod snapshot;
+#[cfg(feature = "async")]
+pub mod stream;
+pub mod surface;
+mod sys;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod timer;
+#[cfg(feature = "wm-wayland")]
+pub mod wayland;
 pub mod x11;
 
 #[cfg(not(feature = "wm-x11-xcb"))]
 pub use bar::run_x11 as run;
 #[cfg(feature = "wm-x11-xcb")]
 pub use bar::run_x11_xcb as run;
+#[cfg(all(feature = "async", not(feature = "wm-x11-xcb")))]
+pub use stream::run_x11_async as run_async;
+#[cfg(all(feature = "async", feature = "wm-x11-xcb"))]
+pub use stream::run_x11_xcb_async as run_async;
@@ -0,0 +1,618 @@
+use crate::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmSurface};
+use crate::config::{BarBuilder, DockDirection};
+use crate::draw::{Color, Painter, Rect};
+use crate::event;
+use crate::surface::Surface;
+use crate::timer::TimerId;
+use core::task::{Context, Poll};
+
+pub type ModuleId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Update {
+    None,
+    Redraw,
+    Resize,
+}
+
+#[derive(Debug, Clone)]
+pub enum ModuleEvent {
+    Click(event::ClickEvent),
+    Scroll(event::ScrollEvent),
+    DoubleClick(event::ClickEvent),
+    MouseMove(event::MotionEvent),
+    HoverEnter,
+    HoverLeave,
+    Timer(TimerId),
+}
+
+/// The sections a bar is split into along its main axis.
+///
+/// On vertical bars `Left` is the top and `Right` the bottom section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Left,
+    Center,
+    Right,
+}
+
+pub trait Module {
+    /// Returns the length the module needs along the bar's main axis, given
+    /// the thickness of the bar.
+    fn measure(&mut self, thickness: u32) -> u32;
+    /// Draws the module. The painter's origin is the module's top-left corner
+    /// and `painter.bounds()` its size.
+    fn draw(&mut self, painter: &mut Painter<Surface>);
+    fn on_event(&mut self, _event: &ModuleEvent) -> Update {
+        Update::None
+    }
+    /// Drives asynchronous work of the module, see `Bar::poll`.
+    fn poll(&mut self, _cx: &mut Context<'_>) -> Poll<Update> {
+        Poll::Pending
+    }
+}
+
+struct Slot {
+    section: Section,
+    module: Box<dyn Module>,
+    extent: u32,
+    rect: Rect,
+    dirty: bool,
+}
+
+/// Arranges modules into left, center and right sections and draws them.
+///
+/// The layout is only recomputed once a module reports `Update::Resize`, the
+/// bar is resized or the docking changes. Otherwise only the modules that
+/// reported `Update::Redraw` are repainted.
+pub struct ModuleContainer {
+    slots: Vec<Slot>,
+    docking: DockDirection,
+    size: (u32, u32),
+    margin: (u32, u32),
+    padding: u32,
+    spacing: u32,
+    background: Color,
+    surface: Option<Surface>,
+    update: Update,
+    hovered: Option<ModuleId>,
+    timers: Vec<(TimerId, ModuleId)>,
+}
+
+impl ModuleContainer {
+    pub fn new(docking: DockDirection) -> Self {
+        Self {
+            slots: Vec::new(),
+            docking,
+            size: (0, 0),
+            margin: (0, 0),
+            padding: 0,
+            spacing: 0,
+            background: Color::TRANSPARENT,
+            surface: None,
+            update: Update::Resize,
+            hovered: None,
+            timers: Vec::new(),
+        }
+    }
+
+    /// Creates a container for a bar built from `cfg`. The bar's margins are
+    /// already applied to its window, so they don't add to the container's.
+    pub fn from_builder(cfg: &BarBuilder) -> Self {
+        Self::new(*cfg.get_docking())
+    }
+
+    pub fn margin(mut self, start: u32, end: u32) -> Self {
+        self.margin = (start, end);
+        self
+    }
+
+    pub fn padding(mut self, padding: u32) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    pub fn spacing(mut self, spacing: u32) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    pub fn background(mut self, background: Color) -> Self {
+        self.background = background;
+        self
+    }
+
+    pub fn push<M: Module + 'static>(&mut self, section: Section, module: M) -> ModuleId {
+        self.slots.push(Slot {
+            section,
+            module: Box::new(module),
+            extent: 0,
+            rect: Rect::default(),
+            dirty: true,
+        });
+        self.update = Update::Resize;
+        self.slots.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn get_mut(&mut self, id: ModuleId) -> Option<&mut (dyn Module + 'static)> {
+        self.slots.get_mut(id).map(|slot| &mut *slot.module)
+    }
+
+    pub fn get_rect(&self, id: ModuleId) -> Option<Rect> {
+        self.slots.get(id).map(|slot| slot.rect)
+    }
+
+    pub fn get_docking(&self) -> DockDirection {
+        self.docking
+    }
+
+    pub fn set_docking(&mut self, docking: DockDirection) {
+        self.docking = docking;
+        self.notify(Update::Resize);
+    }
+
+    pub fn set_size(&mut self, width: u32, height: u32) {
+        if self.size != (width, height) {
+            self.size = (width, height);
+            self.notify(Update::Resize);
+        }
+    }
+
+    /// Marks the whole bar as needing a redraw or, for `Update::Resize`, a
+    /// new layout.
+    pub fn notify(&mut self, update: Update) {
+        if update == Update::Redraw {
+            for slot in self.slots.iter_mut() {
+                slot.dirty = true;
+            }
+        }
+        self.update = self.update.max(update);
+    }
+
+    pub fn needs_redraw(&self) -> bool {
+        self.update != Update::None
+    }
+
+    /// Marks a single module as needing a redraw, e.g. because its state
+    /// changed outside of an event.
+    pub fn request_redraw(&mut self, id: ModuleId) {
+        if let Some(slot) = self.slots.get_mut(id) {
+            slot.dirty = true;
+            self.update = self.update.max(Update::Redraw);
+        }
+    }
+
+    pub fn send_event(&mut self, id: ModuleId, event: &ModuleEvent) -> Update {
+        let update = match self.slots.get_mut(id) {
+            Some(slot) => slot.module.on_event(event),
+            None => return Update::None,
+        };
+        self.apply(id, update);
+        update
+    }
+
+    fn apply(&mut self, id: ModuleId, update: Update) {
+        match update {
+            Update::None => {}
+            Update::Redraw => self.request_redraw(id),
+            Update::Resize => self.notify(update),
+        }
+    }
+
+    /// Polls every module's asynchronous work, as needed by `Bar::poll`, and
+    /// returns the largest update of the modules that made progress.
+    pub fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Update> {
+        let mut result = Poll::Pending;
+        for id in 0..self.slots.len() {
+            if let Poll::Ready(update) = self.slots[id].module.poll(cx) {
+                self.apply(id, update);
+                result = Poll::Ready(match result {
+                    Poll::Ready(max) => update.max(max),
+                    Poll::Pending => update,
+                });
+            }
+        }
+        result
+    }
+
+    pub fn hit_test(&self, x: i32, y: i32) -> Option<ModuleId> {
+        self.slots.iter().position(|slot| slot.rect.contains(x, y))
+    }
+
+    fn module_at(&self, x: i32, y: i32) -> Option<(ModuleId, i32, i32)> {
+        let id = self.hit_test(x, y)?;
+        let rect = self.slots[id].rect;
+        Some((id, x - rect.x, y - rect.y))
+    }
+
+    /// Sends a click to the module under the cursor, with its coordinates
+    /// translated relative to the module.
+    ///
+    /// The click is expected in bar-local coordinates, as delivered to
+    /// `Bar::on_click`.
+    pub fn dispatch_click(&mut self, ev: &event::ClickEvent) -> Option<ModuleId> {
+        let (id, x, y) = self.module_at(ev.x, ev.y)?;
+        let ev = event::ClickEvent { x, y, ..ev.clone() };
+        self.send_event(id, &ModuleEvent::Click(ev));
+        Some(id)
+    }
+
+    pub fn dispatch_scroll(&mut self, ev: &event::ScrollEvent) -> Option<ModuleId> {
+        let (id, x, y) = self.module_at(ev.x, ev.y)?;
+        let ev = event::ScrollEvent { x, y, ..ev.clone() };
+        self.send_event(id, &ModuleEvent::Scroll(ev));
+        Some(id)
+    }
+
+    pub fn dispatch_double_click(&mut self, ev: &event::ClickEvent) -> Option<ModuleId> {
+        let (id, x, y) = self.module_at(ev.x, ev.y)?;
+        let ev = event::ClickEvent { x, y, ..ev.clone() };
+        self.send_event(id, &ModuleEvent::DoubleClick(ev));
+        Some(id)
+    }
+
+    /// Delivers the timer `timer` to the module `id` from now on.
+    pub fn subscribe_timer(&mut self, id: ModuleId, timer: TimerId) {
+        self.timers.push((timer, id));
+    }
+
+    pub fn unsubscribe_timer(&mut self, id: ModuleId, timer: TimerId) {
+        self.timers.retain(|&sub| sub != (timer, id));
+    }
+
+    /// Sends a fired timer to the modules subscribed to it and returns the
+    /// largest update they requested.
+    pub fn dispatch_timer(&mut self, timer: TimerId) -> Update {
+        let ids: Vec<_> = self
+            .timers
+            .iter()
+            .filter(|(t, _)| *t == timer)
+            .map(|&(_, id)| id)
+            .collect();
+        ids.into_iter()
+            .map(|id| self.send_event(id, &ModuleEvent::Timer(timer)))
+            .max()
+            .unwrap_or(Update::None)
+    }
+
+    pub fn get_hovered(&self) -> Option<ModuleId> {
+        self.hovered
+    }
+
+    fn set_hovered(&mut self, hovered: Option<ModuleId>) {
+        if self.hovered == hovered {
+            return;
+        }
+        if let Some(id) = self.hovered {
+            self.send_event(id, &ModuleEvent::HoverLeave);
+        }
+        self.hovered = hovered;
+        if let Some(id) = hovered {
+            self.send_event(id, &ModuleEvent::HoverEnter);
+        }
+    }
+
+    /// Tracks the pointer, sending hover enter and leave events to modules
+    /// as it crosses their boundaries, and forwards the motion to the module
+    /// under the pointer.
+    pub fn dispatch_motion(&mut self, ev: &event::MotionEvent) -> Option<ModuleId> {
+        let target = self.module_at(ev.x, ev.y);
+        self.set_hovered(target.map(|(id, _, _)| id));
+        let (id, x, y) = target?;
+        let ev = event::MotionEvent { x, y, ..ev.clone() };
+        self.send_event(id, &ModuleEvent::MouseMove(ev));
+        Some(id)
+    }
+
+    /// Ends hovering once the pointer left the bar.
+    pub fn dispatch_leave(&mut self) {
+        self.set_hovered(None);
+    }
+
+    fn is_horizontal(&self) -> bool {
+        matches!(self.docking, DockDirection::Top | DockDirection::Bottom)
+    }
+
+    fn axes(&self) -> (u32, u32) {
+        let (w, h) = self.size;
+        if self.is_horizontal() {
+            (w, h)
+        } else {
+            (h, w)
+        }
+    }
+
+    fn section_length(&self, section: Section) -> u32 {
+        let (mut total, mut count) = (0, 0u32);
+        for slot in self.slots.iter().filter(|slot| slot.section == section) {
+            total += slot.extent;
+            count += 1;
+        }
+        total + self.spacing * count.saturating_sub(1)
+    }
+
+    /// The spacing kept towards `section`, none if it is empty.
+    fn spacing_to(&self, section: Section) -> i32 {
+        if self.slots.iter().any(|slot| slot.section == section) {
+            self.spacing as i32
+        } else {
+            0
+        }
+    }
+
+    pub fn layout(&mut self) {
+        let (length, thickness) = self.axes();
+        for slot in self.slots.iter_mut() {
+            slot.extent = slot.module.measure(thickness);
+        }
+        let start = (self.margin.0 + self.padding) as i32;
+        let end = length as i32 - (self.margin.1 + self.padding) as i32;
+        let left_end = start + self.section_length(Section::Left) as i32;
+        let right_start = end - self.section_length(Section::Right) as i32;
+        let center = self.section_length(Section::Center) as i32;
+        let center_start = ((length as i32 - center) / 2)
+            .min(right_start - self.spacing_to(Section::Right) - center)
+            .max(left_end + self.spacing_to(Section::Left));
+        let mut pos = [start, center_start, right_start];
+        let horizontal = self.is_horizontal();
+        let spacing = self.spacing as i32;
+        for slot in self.slots.iter_mut() {
+            let pos = match slot.section {
+                Section::Left => &mut pos[0],
+                Section::Center => &mut pos[1],
+                Section::Right => &mut pos[2],
+            };
+            slot.rect = if horizontal {
+                Rect::new(*pos, 0, slot.extent, thickness)
+            } else {
+                Rect::new(0, *pos, thickness, slot.extent)
+            };
+            *pos += slot.extent as i32 + spacing;
+        }
+    }
+
+    /// Draws the modules that changed onto `surface`, laying them out first
+    /// if necessary, and returns the area that was painted.
+    pub fn draw(&mut self, surface: &mut Surface) -> Rect {
+        let (w, h) = surface.dimensions();
+        self.set_size(w, h);
+        let mut painter = Painter::new(surface);
+        let mut damage = Rect::default();
+        match self.update {
+            Update::None => {}
+            Update::Redraw => {
+                for slot in self.slots.iter_mut().filter(|slot| slot.dirty) {
+                    let mut painter = painter.sub(slot.rect);
+                    painter.clear(self.background);
+                    slot.module.draw(&mut painter);
+                    damage = damage.union(&slot.rect);
+                }
+            }
+            Update::Resize => {
+                self.layout();
+                painter.clear(self.background);
+                for slot in self.slots.iter_mut() {
+                    slot.module.draw(&mut painter.sub(slot.rect));
+                }
+                damage = Rect::new(0, 0, w, h);
+            }
+        }
+        for slot in self.slots.iter_mut() {
+            slot.dirty = false;
+        }
+        self.update = Update::None;
+        damage
+    }
+
+    fn render_rect<'a, B: Bar, Wm: WmAdapterExt<B>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, B>>::AdapterBar,
+        damage: Rect,
+    ) -> Result<(), Wm::Error> {
+        let (w, h) = bar.get_size();
+        let mut surface = match self.surface.take() {
+            Some(surface) if surface.dimensions() == (w, h) => surface,
+            _ => {
+                self.notify(Update::Resize);
+                Surface::new(w, h)
+            }
+        };
+        let damage = self.draw(&mut surface).union(&damage);
+        let surface = <Wm as WmAdapter<B>>::Surface::from_surface(surface);
+        let result = if damage.is_empty() {
+            Ok(())
+        } else {
+            bar.blit_rect(&surface, damage)
+        };
+        self.surface = Some(surface.into_surface());
+        result
+    }
+
+    /// Draws the modules that changed and blits them onto `bar`.
+    pub fn render<'a, B: Bar, Wm: WmAdapterExt<B>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, B>>::AdapterBar,
+    ) -> Result<(), Wm::Error> {
+        self.render_rect::<B, Wm>(bar, Rect::default())
+    }
+
+    /// Like `render`, but also repaints `damage` from the last frame, as
+    /// needed by `Bar::on_draw`.
+    pub fn render_damage<'a, B: Bar, Wm: WmAdapterExt<B>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, B>>::AdapterBar,
+        damage: Rect,
+    ) -> Result<(), Wm::Error> {
+        self.render_rect::<B, Wm>(bar, damage)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// A module of a fixed length that counts how often it is measured.
+    struct Fixed {
+        extent: u32,
+        thickness: Rc<Cell<u32>>,
+        measured: Rc<Cell<u32>>,
+        update: Update,
+    }
+
+    impl Fixed {
+        fn new(extent: u32) -> Self {
+            Self {
+                extent,
+                thickness: Rc::default(),
+                measured: Rc::default(),
+                update: Update::None,
+            }
+        }
+    }
+
+    impl Module for Fixed {
+        fn measure(&mut self, thickness: u32) -> u32 {
+            self.thickness.set(thickness);
+            self.measured.set(self.measured.get() + 1);
+            self.extent
+        }
+
+        fn draw(&mut self, painter: &mut Painter<Surface>) {
+            let bounds = painter.bounds();
+            painter.fill_rect(bounds, Color::WHITE);
+        }
+
+        fn on_event(&mut self, _event: &ModuleEvent) -> Update {
+            self.update
+        }
+    }
+
+    /// Returns the container and the thickness its first module was
+    /// measured with.
+    fn container(docking: DockDirection) -> (ModuleContainer, Rc<Cell<u32>>) {
+        let mut modules = ModuleContainer::new(docking)
+            .margin(5, 7)
+            .padding(2)
+            .spacing(3);
+        let first = Fixed::new(10);
+        let thickness = first.thickness.clone();
+        modules.push(Section::Left, first);
+        modules.push(Section::Left, Fixed::new(20));
+        modules.push(Section::Center, Fixed::new(10));
+        modules.push(Section::Right, Fixed::new(4));
+        modules.push(Section::Right, Fixed::new(6));
+        (modules, thickness)
+    }
+
+    // (start, length) of the modules pushed by `container` on a 100 pixel bar
+    const SPANS: [(i32, u32); 5] = [(7, 10), (20, 20), (45, 10), (78, 4), (85, 6)];
+
+    #[test]
+    fn horizontal_sections() {
+        let (mut modules, thickness) = container(DockDirection::Bottom);
+        modules.set_size(100, 20);
+        modules.layout();
+        for (id, &(x, w)) in SPANS.iter().enumerate() {
+            assert_eq!(modules.get_rect(id), Some(Rect::new(x, 0, w, 20)));
+        }
+        assert_eq!(modules.hit_test(46, 5), Some(2));
+        assert_eq!(modules.hit_test(18, 5), None);
+        assert_eq!(thickness.get(), 20);
+    }
+
+    #[test]
+    fn vertical_sections() {
+        let (mut modules, thickness) = container(DockDirection::Left);
+        modules.set_size(20, 100);
+        modules.layout();
+        for (id, &(y, h)) in SPANS.iter().enumerate() {
+            assert_eq!(modules.get_rect(id), Some(Rect::new(0, y, 20, h)));
+        }
+        assert_eq!(modules.hit_test(5, 46), Some(2));
+        assert_eq!(thickness.get(), 20);
+    }
+
+    #[test]
+    fn center_yields_to_sides() {
+        let mut modules = ModuleContainer::new(DockDirection::Top).spacing(2);
+        modules.push(Section::Left, Fixed::new(30));
+        modules.push(Section::Center, Fixed::new(10));
+        modules.set_size(60, 10);
+        modules.layout();
+        assert_eq!(modules.get_rect(1), Some(Rect::new(32, 0, 10, 10)));
+
+        let mut modules = ModuleContainer::new(DockDirection::Top).spacing(2);
+        modules.push(Section::Center, Fixed::new(10));
+        modules.push(Section::Right, Fixed::new(30));
+        modules.set_size(60, 10);
+        modules.layout();
+        assert_eq!(modules.get_rect(0), Some(Rect::new(18, 0, 10, 10)));
+    }
+
+    #[test]
+    fn lone_center_stays_centered() {
+        let mut modules = ModuleContainer::new(DockDirection::Top).spacing(4);
+        modules.push(Section::Center, Fixed::new(10));
+        modules.set_size(12, 10);
+        modules.layout();
+        assert_eq!(modules.get_rect(0), Some(Rect::new(1, 0, 10, 10)));
+
+        // only the empty side is ignored
+        modules.push(Section::Left, Fixed::new(1));
+        modules.layout();
+        assert_eq!(modules.get_rect(0), Some(Rect::new(5, 0, 10, 10)));
+    }
+
+    #[test]
+    fn layout_only_on_resize() {
+        let mut modules = ModuleContainer::new(DockDirection::Top);
+        let mut module = Fixed::new(10);
+        let measured = module.measured.clone();
+        module.update = Update::Redraw;
+        let redraw = modules.push(Section::Left, module);
+        let mut module = Fixed::new(10);
+        module.update = Update::Resize;
+        let resize = modules.push(Section::Right, module);
+        let mut surface = Surface::new(100, 20);
+
+        assert_eq!(modules.draw(&mut surface), Rect::new(0, 0, 100, 20));
+        assert_eq!(measured.get(), 1);
+        assert!(!modules.needs_redraw());
+        assert!(modules.draw(&mut surface).is_empty());
+
+        let click = event::ClickEvent {
+            bar: 0,
+            x: 5,
+            y: 5,
+            root_x: 5,
+            root_y: 5,
+            button: event::MouseButton::Left,
+            pressed: true,
+            modifiers: 0,
+            time: 0,
+        };
+        assert_eq!(modules.dispatch_click(&click), Some(redraw));
+        assert_eq!(modules.draw(&mut surface), Rect::new(0, 0, 10, 20));
+        assert_eq!(measured.get(), 1);
+
+        let click = event::ClickEvent { x: 95, ..click };
+        assert_eq!(modules.dispatch_click(&click), Some(resize));
+        modules.draw(&mut surface);
+        assert_eq!(measured.get(), 2);
+
+        let mut surface = Surface::new(50, 20);
+        modules.draw(&mut surface);
+        assert_eq!(measured.get(), 3);
+        assert_eq!(modules.get_rect(resize), Some(Rect::new(40, 0, 10, 20)));
+    }
+}
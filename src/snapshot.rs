@@ -0,0 +1,207 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::bar::{run_with, Bar};
+use crate::draw::Color;
+use crate::headless::{HeadlessAdapter, HeadlessScreen};
+use crate::surface::Surface;
+
+/// Setting this environment variable to anything but `0` makes
+/// `Snapshot::check` store the rendered images instead of comparing them.
+pub const BLESS_VAR: &str = "NEO_BAR_BLESS";
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Png(String),
+    Missing(PathBuf),
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Mismatch {
+        pixels: usize,
+        diff: PathBuf,
+    },
+    Custom(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "io error: {}", e),
+            Self::Png(e) => write!(f, "png error: {}", e),
+            Self::Missing(path) => write!(
+                f,
+                "snapshot {} does not exist, run with {}=1 to create it",
+                path.display(),
+                BLESS_VAR
+            ),
+            Self::SizeMismatch { expected, actual } => write!(
+                f,
+                "rendered {}x{} pixels, but the snapshot is {}x{}",
+                actual.0, actual.1, expected.0, expected.1
+            ),
+            Self::Mismatch { pixels, diff } => write!(
+                f,
+                "{} pixels differ from the snapshot, see {}",
+                pixels,
+                diff.display()
+            ),
+            Self::Custom(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(e: png::DecodingError) -> Self {
+        Self::Png(e.to_string())
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(e: png::EncodingError) -> Self {
+        Self::Png(e.to_string())
+    }
+}
+
+/// Runs `bar` on a `HeadlessAdapter` with a single screen of `width` x
+/// `height` pixels until its events are handled and returns the contents of
+/// the first bar.
+pub fn render<B: Bar>(bar: B, width: u32, height: u32) -> Result<Surface, Error> {
+    let screen = HeadlessScreen::new(0, 0, width, height)
+        .named("SNAPSHOT-1")
+        .primary(true);
+    let wm =
+        HeadlessAdapter::with_screens(vec![screen]).map_err(|e| Error::Custom(e.to_string()))?;
+    run_with(bar, &wm).map_err(|e| Error::Custom(format!("failed to run bar ({})", e)))?;
+    wm.bars()
+        .into_iter()
+        .next()
+        .map(|state| state.pixels)
+        .ok_or_else(|| Error::Custom("the bar didn't create any window".to_owned()))
+}
+
+/// Converts a surface to straight RGBA bytes.
+fn to_rgba(surface: &Surface) -> Vec<u8> {
+    surface
+        .data()
+        .iter()
+        .flat_map(|&px| {
+            let c = Color::from_premultiplied(px);
+            [c.r, c.g, c.b, c.a]
+        })
+        .collect()
+}
+
+fn read_png(path: &Path) -> Result<((u32, u32), Vec<u8>), Error> {
+    let mut reader = png::Decoder::new(File::open(path)?).read_info()?;
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data)?;
+    if (info.color_type, info.bit_depth) != (png::ColorType::Rgba, png::BitDepth::Eight) {
+        return Err(Error::Png(format!(
+            "{} is not an 8-bit RGBA image",
+            path.display()
+        )));
+    }
+    data.truncate(info.buffer_size());
+    Ok(((info.width, info.height), data))
+}
+
+fn write_png(path: &Path, (width, height): (u32, u32), data: &[u8]) -> Result<(), Error> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(data)?;
+    writer.finish()?;
+    Ok(())
+}
+
+fn is_blessing() -> bool {
+    std::env::var_os(BLESS_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Compares rendered surfaces against PNG images stored in a directory.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    dir: PathBuf,
+    tolerance: u8,
+}
+
+impl Snapshot {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            tolerance: 0,
+        }
+    }
+
+    /// Sets how far each channel of a pixel may deviate from the snapshot.
+    pub fn tolerance(mut self, tolerance: u8) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Compares `surface` against `<dir>/<name>.png`. On a mismatch the
+    /// differing pixels are painted red into `<dir>/<name>.diff.png`.
+    ///
+    /// If `BLESS_VAR` is set, the snapshot is overwritten instead.
+    pub fn check(&self, name: &str, surface: &Surface) -> Result<(), Error> {
+        let path = self.dir.join(format!("{}.png", name));
+        let diff_path = self.dir.join(format!("{}.diff.png", name));
+        let size = (surface.width(), surface.height());
+        let actual = to_rgba(surface);
+        if is_blessing() {
+            std::fs::create_dir_all(&self.dir)?;
+            let _ = std::fs::remove_file(&diff_path);
+            return write_png(&path, size, &actual);
+        }
+        if !path.exists() {
+            return Err(Error::Missing(path));
+        }
+        let (expected_size, expected) = read_png(&path)?;
+        if expected_size != size {
+            return Err(Error::SizeMismatch {
+                expected: expected_size,
+                actual: size,
+            });
+        }
+        let mut pixels = 0;
+        let diff = expected
+            .chunks_exact(4)
+            .zip(actual.chunks_exact(4))
+            .flat_map(|(e, a)| {
+                let differs = e
+                    .iter()
+                    .zip(a)
+                    .any(|(&e, &a)| e.abs_diff(a) > self.tolerance);
+                if differs {
+                    pixels += 1;
+                    [0xff, 0, 0, 0xff]
+                } else {
+                    // fade matching pixels so the differences stand out
+                    [e[0], e[1], e[2], e[3] / 4]
+                }
+            })
+            .collect::<Vec<u8>>();
+        if pixels == 0 {
+            let _ = std::fs::remove_file(&diff_path);
+            return Ok(());
+        }
+        write_png(&diff_path, size, &diff)?;
+        Err(Error::Mismatch {
+            pixels,
+            diff: diff_path,
+        })
+    }
+}
@@ -0,0 +1,181 @@
+use async_io::{Async, Timer};
+use core::future::{poll_fn, Future};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::Stream;
+use std::os::unix::io::{BorrowedFd, RawFd};
+
+use crate::bar::{quit_signals, Bar, Runner, WmAdapter, WmAdapterExt};
+use crate::error::RunnerError;
+use crate::event;
+use crate::sys::QuitSignals;
+
+fn async_fd<'a>(fd: RawFd) -> std::io::Result<Async<BorrowedFd<'a>>> {
+    // callers only pass descriptors that stay open for at least 'a
+    Async::new_nonblocking(unsafe { BorrowedFd::borrow_raw(fd) })
+}
+
+fn io_error<E: std::error::Error>(e: std::io::Error) -> RunnerError<E> {
+    RunnerError::Custom(format!("failed to wait for events ({})", e))
+}
+
+/// The events of a window manager adapter as an asynchronous stream.
+///
+/// The adapter's file descriptor is registered with the `async-io` reactor,
+/// so waiting for events doesn't block the executor.
+pub struct EventStream<'w, B: Bar, Wm: WmAdapter<B>> {
+    wm: &'w Wm,
+    fd: Async<BorrowedFd<'w>>,
+    _b: core::marker::PhantomData<B>,
+}
+
+impl<'w, B: Bar, Wm: WmAdapter<B>> EventStream<'w, B, Wm> {
+    pub fn new(wm: &'w Wm) -> std::io::Result<Self> {
+        Ok(Self {
+            wm,
+            fd: async_fd(wm.get_fd())?,
+            _b: core::marker::PhantomData,
+        })
+    }
+
+    pub fn poll_event(
+        &self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<event::Event, RunnerError<Wm::Error>>> {
+        loop {
+            match self.wm.poll_event() {
+                Ok(Poll::Ready(ev)) => return Poll::Ready(Ok(ev)),
+                Ok(Poll::Pending) => {}
+                Err(e) => return Poll::Ready(Err(e.into())),
+            }
+            match self.poll_readable(cx) {
+                Poll::Ready(Ok(())) => continue,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+
+    pub async fn next_event(&self) -> Result<event::Event, RunnerError<Wm::Error>> {
+        poll_fn(|cx| self.poll_event(cx)).await
+    }
+
+    fn poll_readable(&self, cx: &mut Context<'_>) -> Poll<Result<(), RunnerError<Wm::Error>>> {
+        self.fd.poll_readable(cx).map_err(io_error)
+    }
+}
+
+impl<'w, B: Bar, Wm: WmAdapter<B>> Stream for EventStream<'w, B, Wm> {
+    type Item = Result<event::Event, RunnerError<Wm::Error>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.poll_event(cx).map(Some)
+    }
+}
+
+/// Like `bar::run`, but waits for events, timers and signals without
+/// blocking, so it can run on an executor next to other futures.
+///
+/// Asynchronous work of the bar, like sockets or subprocesses its modules
+/// read from, is driven in the same task through `Bar::poll`.
+///
+/// This needs the `async-io` reactor, which any executor can drive, e.g.
+/// `async_io::block_on` or `smol`.
+pub async fn run_async<B: Bar, Wm: WmAdapterExt<B>>() -> Result<(), RunnerError<Wm::Error>> {
+    let bar = B::new();
+    let builder = bar.get_bar_builder();
+    let wm = Wm::new(&builder)?;
+    let signals = quit_signals(&bar)?;
+    let runner = Runner::new(bar, &wm, &builder)?;
+    run_async_loop(runner, &wm, signals).await.map(drop)
+}
+
+/// Like `bar::run_with`, but asynchronous like `run_async`.
+pub async fn run_async_with<B: Bar, Wm: WmAdapterExt<B>>(
+    bar: B,
+    wm: &Wm,
+) -> Result<B, RunnerError<Wm::Error>> {
+    let builder = bar.get_bar_builder();
+    let runner = Runner::new(bar, wm, &builder)?;
+    run_async_loop(runner, wm, None).await
+}
+
+async fn run_async_loop<B: Bar, Wm: WmAdapterExt<B>>(
+    mut runner: Runner<'_, B, Wm>,
+    wm: &Wm,
+    mut signals: Option<QuitSignals>,
+) -> Result<B, RunnerError<Wm::Error>> {
+    let signal_fd = signals
+        .as_ref()
+        .map(|s| async_fd(s.get_fd()))
+        .transpose()
+        .map_err(io_error)?;
+    let events = EventStream::new(wm).map_err(io_error)?;
+    loop {
+        runner.fire_timers();
+        let ev = match wm.poll_event()? {
+            Poll::Ready(ev) => ev,
+            Poll::Pending => {
+                if runner.draw() {
+                    continue;
+                }
+                let mut timer = runner.next_deadline().map(Timer::at);
+                poll_fn(|cx| {
+                    let mut ready = runner.poll_bar(cx).is_ready();
+                    if let Poll::Ready(res) = events.poll_readable(cx) {
+                        res?;
+                        ready = true;
+                    }
+                    if let Some(Poll::Ready(res)) =
+                        signal_fd.as_ref().map(|fd| fd.poll_readable(cx))
+                    {
+                        res.map_err(io_error)?;
+                        ready = true;
+                    }
+                    if let Some(timer) = timer.as_mut() {
+                        ready |= Pin::new(timer).poll(cx).is_ready();
+                    }
+                    if ready {
+                        Poll::Ready(Ok::<_, RunnerError<Wm::Error>>(()))
+                    } else {
+                        Poll::Pending
+                    }
+                })
+                .await?;
+                if signals.as_mut().is_some_and(|s| s.take_pending()) {
+                    event::Event::Quit
+                } else {
+                    continue;
+                }
+            }
+        };
+        match runner.handle(ev) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => {
+                runner.abort();
+                return Err(e);
+            }
+        }
+    }
+    drop(signal_fd);
+    drop(signals);
+    runner.quit()
+}
+
+pub async fn run_x11_async<B: Bar>() -> Result<(), RunnerError<crate::x11::X11RustAdapterError<B>>>
+{
+    run_async::<B, crate::x11::X11RustAdapter<B>>().await
+}
+
+#[cfg(feature = "wm-x11-xcb")]
+pub async fn run_x11_xcb_async<B: Bar>(
+) -> Result<(), RunnerError<crate::x11::X11XcbAdapterError<B>>> {
+    run_async::<B, crate::x11::X11XcbAdapter<B>>().await
+}
+
+#[cfg(feature = "wm-wayland")]
+pub async fn run_wayland_async<B: Bar>(
+) -> Result<(), RunnerError<crate::wayland::WaylandAdapterError<B>>> {
+    run_async::<B, crate::wayland::WaylandAdapter<B>>().await
+}
@@ -0,0 +1,120 @@
+use crate::bar::WmSurface;
+use crate::draw::Rect;
+
+/// A CPU-side pixel buffer.
+///
+/// Pixels are stored row by row as native-endian `0xAARRGGBB` values with
+/// premultiplied alpha.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Surface {
+    width: u32,
+    height: u32,
+    data: Vec<u32>,
+}
+
+impl Surface {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            data: vec![0; width as usize * height as usize],
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &[u32] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u32] {
+        &mut self.data
+    }
+
+    pub fn row(&self, y: u32) -> &[u32] {
+        let start = y as usize * self.width as usize;
+        &self.data[start..start + self.width as usize]
+    }
+
+    pub fn row_mut(&mut self, y: u32) -> &mut [u32] {
+        let start = y as usize * self.width as usize;
+        &mut self.data[start..start + self.width as usize]
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<u32> {
+        if x < self.width && y < self.height {
+            Some(self.data[(y * self.width + x) as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, argb: u32) {
+        if x < self.width && y < self.height {
+            self.data[(y * self.width + x) as usize] = argb;
+        }
+    }
+
+    pub fn fill(&mut self, argb: u32) {
+        for px in self.data.iter_mut() {
+            *px = argb;
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.data.clear();
+        self.data.resize(width as usize * height as usize, 0);
+    }
+
+    /// Copies `rect` of `src` to `(x, y)`, replacing the pixels there.
+    /// Pixels outside of either surface are skipped.
+    pub fn copy_rect(&mut self, src: &Surface, rect: Rect, x: i32, y: i32) {
+        let dst = Rect::new(x, y, rect.width, rect.height).intersect(&Rect::new(
+            0,
+            0,
+            self.width,
+            self.height,
+        ));
+        let (dx, dy) = (rect.x - x, rect.y - y);
+        for py in dst.y..dst.bottom() {
+            for px in dst.x..dst.right() {
+                let (sx, sy) = (px + dx, py + dy);
+                if sx < 0 || sy < 0 {
+                    continue;
+                }
+                if let Some(argb) = src.get_pixel(sx as u32, sy as u32) {
+                    self.set_pixel(px as u32, py as u32, argb);
+                }
+            }
+        }
+    }
+}
+
+impl WmSurface for Surface {
+    fn new(width: u32, height: u32) -> Self {
+        Surface::new(width, height)
+    }
+    fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+    fn pixels(&self) -> &[u32] {
+        &self.data
+    }
+    fn pixels_mut(&mut self) -> &mut [u32] {
+        &mut self.data
+    }
+    fn from_surface(surface: Surface) -> Self {
+        surface
+    }
+    fn into_surface(self) -> Surface {
+        self
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{Error, ErrorKind, Read};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::time::Duration;
+
+/// Blocks until one of `fds` becomes readable or `timeout` passed and
+/// returns which ones are readable.
+pub(crate) fn wait_readable(fds: &[RawFd], timeout: Option<Duration>) -> Result<Vec<bool>, Error> {
+    // round up, waking up early would only cause another wait
+    let timeout = timeout.map_or(-1, |t| {
+        t.as_nanos()
+            .div_ceil(1_000_000)
+            .min(libc::c_int::MAX as u128) as libc::c_int
+    });
+    let mut pollfds: Vec<_> = fds
+        .iter()
+        .map(|&fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        })
+        .collect();
+    loop {
+        let res =
+            unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as libc::nfds_t, timeout) };
+        if res >= 0 {
+            break;
+        }
+        let err = Error::last_os_error();
+        if err.kind() != ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    Ok(pollfds.iter().map(|p| p.revents != 0).collect())
+}
+
+pub(crate) fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned())
+}
+
+/// Turns SIGINT, SIGTERM and SIGHUP into a readable file descriptor.
+pub(crate) struct QuitSignals {
+    read: UnixStream,
+    write: UnixStream,
+    ids: Vec<signal_hook::SigId>,
+}
+
+impl QuitSignals {
+    pub fn new() -> Result<Self, Error> {
+        let (read, write) = UnixStream::pair()?;
+        read.set_nonblocking(true)?;
+        write.set_nonblocking(true)?;
+        let mut slf = Self {
+            read,
+            write,
+            ids: Vec::new(),
+        };
+        for &sig in &[
+            signal_hook::consts::SIGINT,
+            signal_hook::consts::SIGTERM,
+            signal_hook::consts::SIGHUP,
+        ] {
+            let id = signal_hook::low_level::pipe::register_raw(sig, slf.write.as_raw_fd())?;
+            slf.ids.push(id);
+        }
+        Ok(slf)
+    }
+
+    pub fn get_fd(&self) -> RawFd {
+        self.read.as_raw_fd()
+    }
+
+    /// Returns whether a signal arrived since the last call.
+    pub fn take_pending(&mut self) -> bool {
+        let mut buf = [0u8; 16];
+        let mut pending = false;
+        while let Ok(n) = self.read.read(&mut buf) {
+            if n == 0 {
+                break;
+            }
+            pending = true;
+        }
+        pending
+    }
+}
+
+impl Drop for QuitSignals {
+    fn drop(&mut self) {
+        for id in self.ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+    }
+}
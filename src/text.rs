@@ -0,0 +1,355 @@
+//! Font loading, glyph caching and single-line text layout.
+//!
+//! Text is shaped with rustybuzz, so ligatures, contextual forms, kerning and
+//! mark positioning work as the font defines them. Runs of text drawn with
+//! different fonts are placed left to right without bidirectional
+//! reordering.
+
+use ab_glyph::{Font as _, FontArc, GlyphId, PxScale, ScaleFont};
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::bar::WmSurface;
+use crate::draw::{Color, Painter};
+
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    InvalidFont,
+    NotFound(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read font: {}", e),
+            Self::InvalidFont => write!(f, "invalid font data"),
+            Self::NotFound(name) => write!(f, "no font matching '{}' found", name),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+static NEXT_FONT_ID: AtomicU32 = AtomicU32::new(0);
+
+#[derive(Clone)]
+pub struct Font {
+    id: u32,
+    font: FontArc,
+}
+
+impl std::fmt::Debug for Font {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Font").field("id", &self.id).finish()
+    }
+}
+
+impl Font {
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let font = FontArc::try_from_vec(data).map_err(|_| Error::InvalidFont)?;
+        // the shaper parses the tables it needs on its own
+        rustybuzz::Face::from_slice(font.font_data(), 0).ok_or(Error::InvalidFont)?;
+        Ok(Self {
+            id: NEXT_FONT_ID.fetch_add(1, Ordering::Relaxed),
+            font,
+        })
+    }
+
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Self::from_bytes(std::fs::read(path)?)
+    }
+
+    /// Looks up a font by family name (or any fontconfig pattern) using
+    /// `fc-match`.
+    ///
+    /// `fc-match` falls back to some default font for unknown families, so
+    /// the match is rejected unless its family is the requested one or the
+    /// request names a generic family like `monospace`.
+    pub fn from_family(family: &str) -> Result<Self, Error> {
+        let out = std::process::Command::new("fc-match")
+            .args(["--format=%{file}\n%{family}", family])
+            .output()?;
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let (path, families) = stdout.split_once('\n').unwrap_or((&stdout, ""));
+        if !out.status.success() || path.is_empty() || !family_matches(family, families) {
+            return Err(Error::NotFound(family.to_owned()));
+        }
+        Self::from_path(path)
+    }
+
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph_id(c).0 != 0
+    }
+}
+
+/// The aliases fontconfig resolves to a configured family.
+const GENERIC_FAMILIES: [&str; 10] = [
+    "sans-serif",
+    "sans",
+    "serif",
+    "monospace",
+    "mono",
+    "emoji",
+    "math",
+    "cursive",
+    "fantasy",
+    "system-ui",
+];
+
+/// Checks whether the comma separated `families` of a matched font satisfy
+/// the family requested in the fontconfig `pattern`.
+fn family_matches(pattern: &str, families: &str) -> bool {
+    let requested = pattern.split(':').next().unwrap_or("");
+    let requested = requested.split(',').next().unwrap_or("").trim();
+    // a trailing "-<size>" is not part of the name
+    let requested = match requested.rsplit_once('-') {
+        Some((name, size)) if size.parse::<f32>().is_ok() => name.trim(),
+        _ => requested,
+    };
+    requested.is_empty()
+        || GENERIC_FAMILIES
+            .iter()
+            .any(|g| g.eq_ignore_ascii_case(requested))
+        || families
+            .split(',')
+            .any(|f| f.trim().eq_ignore_ascii_case(requested))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TextExtents {
+    pub width: u32,
+    pub height: u32,
+    pub ascent: f32,
+    pub descent: f32,
+}
+
+#[derive(Debug)]
+struct RasterGlyph {
+    left: i32,
+    top: i32,
+    width: u32,
+    mask: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PositionedGlyph {
+    font: usize,
+    id: GlyphId,
+    x: f32,
+    /// The offset from the baseline, downwards.
+    y: f32,
+}
+
+/// Lays out and draws single lines of text at a fixed pixel size.
+///
+/// Characters missing from the first font are looked up in the following
+/// fonts, so icon fonts can be appended as fallbacks. Rasterized glyphs are
+/// cached per font, size and glyph id.
+#[derive(Debug)]
+pub struct TextRenderer {
+    fonts: Vec<Font>,
+    size: f32,
+    cache: HashMap<(u32, u32, GlyphId), Option<Rc<RasterGlyph>>>,
+}
+
+impl TextRenderer {
+    pub fn new(font: Font, size: f32) -> Self {
+        Self {
+            fonts: vec![font],
+            size,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn fallback(mut self, font: Font) -> Self {
+        self.fonts.push(font);
+        self
+    }
+
+    pub fn push_fallback(&mut self, font: Font) {
+        self.fonts.push(font);
+    }
+
+    pub fn get_size(&self) -> f32 {
+        self.size
+    }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size;
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
+    fn scale(&self) -> PxScale {
+        PxScale::from(self.size)
+    }
+
+    fn font_for(&self, c: char) -> usize {
+        self.fonts.iter().position(|f| f.has_glyph(c)).unwrap_or(0)
+    }
+
+    /// Splits `text` into runs of characters drawn with the same font.
+    fn runs(&self, text: &str) -> Vec<(usize, String)> {
+        let mut runs: Vec<(usize, String)> = Vec::new();
+        for c in text.chars().filter(|c| !c.is_control()) {
+            let font = self.font_for(c);
+            match runs.last_mut() {
+                Some((last, run)) if *last == font => run.push(c),
+                _ => runs.push((font, c.to_string())),
+            }
+        }
+        runs
+    }
+
+    fn layout(&self, text: &str) -> (Vec<PositionedGlyph>, f32) {
+        let scale = self.scale();
+        let mut glyphs = Vec::with_capacity(text.len());
+        let mut x = 0.0;
+        for (font, run) in self.runs(text) {
+            let data = self.fonts[font].font.font_data();
+            let face = match rustybuzz::Face::from_slice(data, 0) {
+                Some(face) => face,
+                None => continue,
+            };
+            let mut buffer = rustybuzz::UnicodeBuffer::new();
+            buffer.push_str(&run);
+            buffer.guess_segment_properties();
+            let shaped = rustybuzz::shape(&face, &[], buffer);
+            // the shaper works in font units
+            let scaled = self.fonts[font].font.as_scaled(scale);
+            let (h, v) = (scaled.h_scale_factor(), scaled.v_scale_factor());
+            for (info, pos) in shaped.glyph_infos().iter().zip(shaped.glyph_positions()) {
+                glyphs.push(PositionedGlyph {
+                    font,
+                    id: GlyphId(info.glyph_id as u16),
+                    x: x + pos.x_offset as f32 * h,
+                    y: -pos.y_offset as f32 * v,
+                });
+                x += pos.x_advance as f32 * h;
+            }
+        }
+        (glyphs, x)
+    }
+
+    pub fn measure(&self, text: &str) -> TextExtents {
+        let scaled = self.fonts[0].font.as_scaled(self.scale());
+        let (ascent, descent) = (scaled.ascent(), scaled.descent());
+        let (_, width) = self.layout(text);
+        TextExtents {
+            width: width.ceil() as u32,
+            height: (ascent - descent).ceil() as u32,
+            ascent,
+            descent,
+        }
+    }
+
+    fn raster(&mut self, font: usize, id: GlyphId) -> Option<Rc<RasterGlyph>> {
+        let font = &self.fonts[font];
+        let scale = self.scale();
+        let key = (font.id, scale.y.to_bits(), id);
+        self.cache
+            .entry(key)
+            .or_insert_with(|| {
+                let outline = font.font.outline_glyph(id.with_scale(scale))?;
+                let bounds = outline.px_bounds();
+                let width = bounds.width() as u32;
+                let mut mask = vec![0; width as usize * bounds.height() as usize];
+                outline.draw(|x, y, c| {
+                    mask[(y * width + x) as usize] = (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+                });
+                Some(Rc::new(RasterGlyph {
+                    left: bounds.min.x as i32,
+                    top: bounds.min.y as i32,
+                    width,
+                    mask,
+                }))
+            })
+            .clone()
+    }
+
+    /// Draws `text` with the top-left corner of its line box at (`x`, `y`) and
+    /// returns the extents of the drawn text.
+    pub fn draw<S: WmSurface>(
+        &mut self,
+        painter: &mut Painter<S>,
+        x: i32,
+        y: i32,
+        text: &str,
+        color: Color,
+    ) -> TextExtents {
+        let extents = self.measure(text);
+        let baseline = y + extents.ascent.round() as i32;
+        let (glyphs, _) = self.layout(text);
+        for glyph in glyphs {
+            if let Some(raster) = self.raster(glyph.font, glyph.id) {
+                let gx = x + glyph.x.round() as i32 + raster.left;
+                let gy = baseline + glyph.y.round() as i32 + raster.top;
+                painter.draw_mask(gx, gy, raster.width, &raster.mask, color);
+            }
+        }
+        extents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn family_must_match() {
+        assert!(family_matches("DejaVu Sans", "DejaVu Sans"));
+        assert!(family_matches("dejavu sans:bold", "DejaVu Sans"));
+        assert!(family_matches("Noto Sans-12", "Noto Sans,Noto Sans UI"));
+        assert!(family_matches("Noto Sans UI", "Noto Sans,Noto Sans UI"));
+        assert!(!family_matches("No Such Font", "DejaVu Sans"));
+        assert!(!family_matches("DejaVu", "DejaVu Sans"));
+    }
+
+    fn dejavu_sans() -> TextRenderer {
+        let font = Font::from_family("DejaVu Sans").expect("the shaping tests need DejaVu Sans");
+        TextRenderer::new(font, 20.0)
+    }
+
+    fn glyph_ids(text: &TextRenderer, s: &str) -> Vec<u16> {
+        text.layout(s).0.iter().map(|g| g.id.0).collect()
+    }
+
+    #[test]
+    fn ligatures_are_shaped() {
+        let text = dejavu_sans();
+        let f = text.fonts[0].font.glyph_id('f').0;
+        let ligature = glyph_ids(&text, "fi");
+        assert_eq!(ligature.len(), 1);
+        assert_ne!(ligature[0], f);
+        // lam and alef join into one glyph
+        assert_eq!(glyph_ids(&text, "\u{644}\u{627}").len(), 1);
+    }
+
+    #[test]
+    fn runs_follow_font_coverage() {
+        let text = dejavu_sans().fallback(dejavu_sans().fonts[0].clone());
+        assert_eq!(text.runs("ab\ncd"), vec![(0, "abcd".to_owned())]);
+        let measured = text.measure("fi");
+        let (glyphs, width) = text.layout("fi");
+        assert_eq!(measured.width, width.ceil() as u32);
+        assert_eq!(glyphs[0].x, 0.0);
+    }
+
+    #[test]
+    fn generic_families_accept_any_match() {
+        assert!(family_matches("monospace", "DejaVu Sans Mono"));
+        assert!(family_matches("Sans-Serif:italic", "DejaVu Sans"));
+        assert!(family_matches(":weight=bold", "DejaVu Sans"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub type TimerId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Schedule {
+    /// Fires once after the given delay.
+    Once(Duration),
+    /// Fires repeatedly, the first time one period after it was added.
+    Interval(Duration),
+    /// Fires whenever the wall clock passes a multiple of the period, e.g.
+    /// at every full minute for `Aligned(Duration::from_secs(60))`.
+    ///
+    /// Periods are aligned to UTC, so periods longer than an hour don't line
+    /// up with local time in every time zone.
+    Aligned(Duration),
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: TimerId,
+    schedule: Schedule,
+    deadline: Instant,
+}
+
+/// The timers of a running bar.
+#[derive(Debug, Clone, Default)]
+pub struct Timers {
+    entries: Vec<Entry>,
+    next_id: TimerId,
+}
+
+/// The wall-clock time as a duration since the Unix epoch.
+fn wall_clock() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+fn until_aligned(period: Duration, wall: Duration) -> Duration {
+    let period = period.as_nanos().max(1);
+    Duration::from_nanos((period - wall.as_nanos() % period) as u64)
+}
+
+impl Schedule {
+    fn first_deadline(&self, now: Instant, wall: Duration) -> Instant {
+        match *self {
+            Self::Once(delay) | Self::Interval(delay) => now + delay,
+            Self::Aligned(period) => now + until_aligned(period, wall),
+        }
+    }
+
+    fn next_deadline(&self, last: Instant, now: Instant, wall: Duration) -> Option<Instant> {
+        match *self {
+            Self::Once(_) => None,
+            // skip the ticks that were missed instead of firing them in a burst
+            Self::Interval(period) if last + period <= now => Some(now + period),
+            Self::Interval(period) => Some(last + period),
+            // realign every time so the timer follows changes of the clock;
+            // a boundary that is only moments away is the one that just passed
+            // as seen by a slightly different clock
+            Self::Aligned(period) => {
+                let next = until_aligned(period, wall);
+                Some(
+                    now + if next < period / 100 {
+                        next + period
+                    } else {
+                        next
+                    },
+                )
+            }
+        }
+    }
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, schedule: Schedule) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.entries.push(Entry {
+            id,
+            schedule,
+            deadline: schedule.first_deadline(Instant::now(), wall_clock()),
+        });
+        id
+    }
+
+    /// Removes a timer. Returns whether the timer was still active.
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|e| e.id != id);
+        self.entries.len() != len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.entries.iter().map(|e| e.deadline).min()
+    }
+
+    /// Returns the time until the next timer fires, zero if one is overdue.
+    pub fn timeout(&self, now: Instant) -> Option<Duration> {
+        self.next_deadline()
+            .map(|deadline| deadline.saturating_duration_since(now))
+    }
+
+    /// Returns the timers that are due at `now`, rescheduling repeating
+    /// timers and removing one-shot ones.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<TimerId> {
+        let wall = wall_clock();
+        let mut expired = Vec::new();
+        self.entries.retain_mut(|e| {
+            if e.deadline > now {
+                return true;
+            }
+            expired.push(e.id);
+            match e.schedule.next_deadline(e.deadline, now, wall) {
+                Some(deadline) => {
+                    e.deadline = deadline;
+                    true
+                }
+                None => false,
+            }
+        });
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(s: f64) -> Duration {
+        Duration::from_secs_f64(s)
+    }
+
+    #[test]
+    fn aligned_to_minutes_and_seconds() {
+        let minute = secs(60.0);
+        assert_eq!(until_aligned(minute, secs(125.5)), secs(54.5));
+        // exactly on a boundary waits for the next one
+        assert_eq!(until_aligned(minute, secs(120.0)), minute);
+        assert_eq!(until_aligned(secs(1.0), secs(10.25)), secs(0.75));
+
+        let now = Instant::now();
+        let schedule = Schedule::Aligned(minute);
+        assert_eq!(schedule.first_deadline(now, secs(90.0)), now + secs(30.0));
+    }
+
+    #[test]
+    fn aligned_skips_boundary_that_just_passed() {
+        let (now, minute) = (Instant::now(), secs(60.0));
+        let schedule = Schedule::Aligned(minute);
+        // the wall clock lags behind the monotonic one that woke the loop
+        let next = schedule.next_deadline(now, now, secs(119.999));
+        assert_eq!(next, Some(now + secs(60.001)));
+        let next = schedule.next_deadline(now, now, secs(120.2));
+        assert_eq!(next, Some(now + secs(59.8)));
+    }
+
+    #[test]
+    fn interval_does_not_drift() {
+        let (start, period) = (Instant::now(), secs(1.0));
+        let schedule = Schedule::Interval(period);
+        let deadline = schedule.first_deadline(start, Duration::ZERO);
+        assert_eq!(deadline, start + period);
+        // a late wakeup keeps the original phase
+        let late = deadline + secs(0.3);
+        let next = schedule.next_deadline(deadline, late, Duration::ZERO);
+        assert_eq!(next, Some(start + secs(2.0)));
+        // missed ticks are skipped
+        let very_late = deadline + secs(2.5);
+        let next = schedule.next_deadline(deadline, very_late, Duration::ZERO);
+        assert_eq!(next, Some(very_late + period));
+    }
+
+    #[test]
+    fn expired_timers_are_rescheduled() {
+        let mut timers = Timers::new();
+        let once = timers.add(Schedule::Once(secs(1.0)));
+        let interval = timers.add(Schedule::Interval(secs(2.0)));
+        let first = timers.next_deadline().unwrap();
+        let tick = timers.entries[1].deadline;
+        let now = first - secs(1.0);
+        assert_eq!(timers.timeout(now), Some(secs(1.0)));
+        assert_eq!(timers.timeout(tick), Some(Duration::ZERO));
+
+        assert!(timers.take_expired(now).is_empty());
+        assert_eq!(timers.take_expired(first), [once]);
+        assert_eq!(timers.take_expired(tick), [interval]);
+        assert_eq!(timers.next_deadline(), Some(tick + secs(2.0)));
+        assert!(timers.take_expired(tick + secs(1.0)).is_empty());
+        assert_eq!(timers.take_expired(tick + secs(2.0)), [interval]);
+    }
+
+    #[test]
+    fn cancel() {
+        let mut timers = Timers::new();
+        let a = timers.add(Schedule::Once(secs(1.0)));
+        let b = timers.add(Schedule::Interval(secs(1.0)));
+        assert_ne!(a, b);
+        assert!(timers.cancel(b));
+        assert!(!timers.cancel(b));
+        let deadline = timers.next_deadline().unwrap();
+        assert_eq!(timers.take_expired(deadline + secs(5.0)), [a]);
+        // fired one-shot timers are gone as well
+        assert!(!timers.cancel(a));
+        assert!(timers.is_empty());
+        // ids are not reused
+        assert!(timers.add(Schedule::Once(secs(1.0))) > b);
+    }
+}
@@ -0,0 +1,37 @@
+#[derive(Debug)]
+pub enum Error {
+    WaylandError(wayland_client::backend::WaylandError),
+    WaylandConError(wayland_client::ConnectError),
+    DispatchError(wayland_client::DispatchError),
+    IoError(std::io::Error),
+    Custom(String),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::WaylandError(e) => write!(f, "wayland error: {}", e),
+            Self::WaylandConError(e) => write!(f, "wayland connect error: {}", e),
+            Self::DispatchError(e) => write!(f, "wayland dispatch error: {}", e),
+            Self::IoError(e) => write!(f, "io error: {}", e),
+            Self::Custom(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+macro_rules! impl_err {
+    ($a:ty, $b:ident) => {
+        impl From<$a> for Error {
+            fn from(e: $a) -> Self {
+                Self::$b(e)
+            }
+        }
+    };
+}
+
+impl_err!(wayland_client::backend::WaylandError, WaylandError);
+impl_err!(wayland_client::ConnectError, WaylandConError);
+impl_err!(wayland_client::DispatchError, DispatchError);
+impl_err!(std::io::Error, IoError);
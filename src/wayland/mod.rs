@@ -0,0 +1,11 @@
+mod error;
+mod screen;
+mod shm;
+mod wm;
+
+#[doc(inline)]
+pub use screen::WaylandScreen;
+#[doc(inline)]
+pub use wm::*;
+
+pub type WaylandAdapterError<B> = <WaylandAdapter<B> as super::bar::WmAdapter<B>>::Error;
@@ -0,0 +1,153 @@
+use wayland_client::protocol::wl_output::{self, WlOutput};
+use wayland_client::{Proxy, WEnum};
+
+use crate::bar::WmScreen;
+
+/// An output of the compositor.
+///
+/// Positions and sizes are in the compositor's logical coordinates, i.e. the
+/// mode divided by the output scale. Bars on the output are drawn at
+/// [`scale`](Self::scale) times their logical size.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaylandScreen {
+    pub(crate) output: WlOutput,
+    pos: (i32, i32),
+    size: (u32, u32),
+    physical_size: Option<(u32, u32)>,
+    name: Option<String>,
+    scale: u32,
+    pub(crate) primary: bool,
+}
+
+impl WaylandScreen {
+    /// The integer scale of the output, the number of buffer pixels per
+    /// logical pixel.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+}
+
+impl WmScreen for WaylandScreen {
+    fn dimensions(&self) -> (u32, u32) {
+        self.size
+    }
+    fn physical_dimensions(&self) -> Option<(f32, f32)> {
+        self.physical_size.map(|(w, h)| (w as f32, h as f32))
+    }
+    fn position(&self) -> (i32, i32) {
+        self.pos
+    }
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+/// A bound `wl_output` global and the state announced for it so far.
+#[derive(Debug)]
+pub(crate) struct Output {
+    pub(crate) global: u32,
+    pending: WaylandScreen,
+    mode: (i32, i32),
+    scale: i32,
+    rotated: bool,
+    /// The state as of the last `done` event.
+    pub(crate) screen: Option<WaylandScreen>,
+}
+
+impl Output {
+    pub(crate) fn new(global: u32, output: WlOutput) -> Self {
+        Self {
+            global,
+            pending: WaylandScreen {
+                output,
+                pos: (0, 0),
+                size: (0, 0),
+                physical_size: None,
+                name: None,
+                scale: 1,
+                primary: false,
+            },
+            mode: (0, 0),
+            scale: 1,
+            rotated: false,
+            screen: None,
+        }
+    }
+
+    /// Applies an output event. Returns whether the announced state changed.
+    pub(crate) fn handle_event(&mut self, event: wl_output::Event) -> bool {
+        match event {
+            wl_output::Event::Geometry {
+                x,
+                y,
+                physical_width,
+                physical_height,
+                transform,
+                ..
+            } => {
+                self.pending.pos = (x, y);
+                self.pending.physical_size = Some((physical_width, physical_height))
+                    .filter(|&(w, h)| w > 0 && h > 0)
+                    .map(|(w, h)| (w as u32, h as u32));
+                self.rotated = matches!(
+                    transform,
+                    WEnum::Value(
+                        wl_output::Transform::_90
+                            | wl_output::Transform::_270
+                            | wl_output::Transform::Flipped90
+                            | wl_output::Transform::Flipped270
+                    )
+                );
+            }
+            wl_output::Event::Mode {
+                flags: WEnum::Value(flags),
+                width,
+                height,
+                ..
+            } if flags.contains(wl_output::Mode::Current) => self.mode = (width, height),
+            wl_output::Event::Scale { factor } => self.scale = factor.max(1),
+            wl_output::Event::Name { name } => self.pending.name = Some(name),
+            wl_output::Event::Done => {
+                let (w, h) = if self.rotated {
+                    (self.mode.1, self.mode.0)
+                } else {
+                    self.mode
+                };
+                self.pending.size = (
+                    (w / self.scale).max(0) as u32,
+                    (h / self.scale).max(0) as u32,
+                );
+                self.pending.scale = self.scale as u32;
+                let changed = self.screen.as_ref() != Some(&self.pending);
+                self.screen = Some(self.pending.clone());
+                return changed;
+            }
+            _ => {}
+        }
+        false
+    }
+
+    /// Destroys the proxy of an output the compositor removed.
+    pub(crate) fn release(self) {
+        if self.pending.output.version() >= 3 {
+            self.pending.output.release();
+        }
+    }
+}
+
+/// Lists the announced outputs. The first one counts as primary, as Wayland
+/// has no notion of a primary output.
+pub(crate) fn list_screens(outputs: &[Output]) -> Vec<WaylandScreen> {
+    outputs
+        .iter()
+        .filter_map(|o| o.screen.clone())
+        .enumerate()
+        .map(|(i, mut screen)| {
+            screen.primary = i == 0;
+            screen
+        })
+        .collect()
+}
@@ -0,0 +1,124 @@
+use super::error::Error;
+use core::convert::TryInto;
+use std::os::unix::io::{AsFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wayland_client::protocol::wl_buffer::WlBuffer;
+use wayland_client::protocol::wl_shm::{self, WlShm};
+use wayland_client::protocol::wl_shm_pool::WlShmPool;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::{Dispatch, QueueHandle};
+
+use crate::surface::Surface;
+
+/// A `wl_buffer` backed by a memory-mapped file.
+#[derive(Debug)]
+pub(crate) struct ShmBuffer {
+    buffer: WlBuffer,
+    addr: *mut u8,
+    size: (u32, u32),
+    // cleared by the compositor's release event
+    busy: Arc<AtomicBool>,
+}
+
+fn memfd(len: usize) -> Result<OwnedFd, Error> {
+    let fd = unsafe {
+        libc::memfd_create(
+            b"neo-bar\0".as_ptr() as *const libc::c_char,
+            libc::MFD_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let file = std::fs::File::from(fd);
+    file.set_len(len as u64)?;
+    Ok(file.into())
+}
+
+impl ShmBuffer {
+    pub fn new<D>(
+        shm: &WlShm,
+        (width, height): (u32, u32),
+        transparent: bool,
+        qh: &QueueHandle<D>,
+    ) -> Result<Self, Error>
+    where
+        D: Dispatch<WlShmPool, ()> + Dispatch<WlBuffer, Arc<AtomicBool>> + 'static,
+    {
+        let len = width as usize * height as usize * 4;
+        let too_large = || Error::Custom(format!("bar of {}x{} is too large", width, height));
+        let pool_len = len.try_into().map_err(|_| too_large())?;
+        let fd = memfd(len)?;
+        let addr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                std::os::unix::io::AsRawFd::as_raw_fd(&fd),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let format = if transparent {
+            wl_shm::Format::Argb8888
+        } else {
+            wl_shm::Format::Xrgb8888
+        };
+        let busy = Arc::new(AtomicBool::new(false));
+        let pool = shm.create_pool(fd.as_fd(), pool_len, qh, ());
+        let buffer = pool.create_buffer(
+            0,
+            width as i32,
+            height as i32,
+            width as i32 * 4,
+            format,
+            qh,
+            busy.clone(),
+        );
+        // the buffer keeps the memory alive on the compositor's side
+        pool.destroy();
+        Ok(Self {
+            buffer,
+            addr: addr as *mut u8,
+            size: (width, height),
+            busy,
+        })
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.size
+    }
+
+    /// Whether the compositor may still read the buffer.
+    pub fn is_busy(&self) -> bool {
+        self.busy.load(Ordering::Acquire)
+    }
+
+    /// Copies `surface`, which has to be the size of the buffer.
+    pub fn write(&mut self, surface: &Surface) {
+        let len = self.size.0 as usize * self.size.1 as usize;
+        let data = unsafe { core::slice::from_raw_parts_mut(self.addr as *mut u32, len) };
+        // wl_shm formats are little-endian
+        for (dst, src) in data.iter_mut().zip(surface.data()) {
+            *dst = src.to_le();
+        }
+    }
+
+    pub fn attach(&self, surface: &WlSurface) {
+        self.busy.store(true, Ordering::Release);
+        surface.attach(Some(&self.buffer), 0, 0);
+    }
+}
+
+impl Drop for ShmBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+        let len = self.size.0 as usize * self.size.1 as usize * 4;
+        unsafe { libc::munmap(self.addr as *mut libc::c_void, len) };
+    }
+}
@@ -0,0 +1,942 @@
+pub use super::error::Error;
+use core::cell::{Cell, RefCell};
+use core::task::Poll;
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use wayland_client::backend::WaylandError;
+use wayland_client::protocol::{
+    wl_buffer, wl_compositor, wl_output, wl_pointer, wl_registry, wl_seat, wl_shm, wl_shm_pool,
+    wl_surface,
+};
+use wayland_client::{delegate_noop, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum};
+use wayland_protocols_wlr::layer_shell::v1::client::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+
+use super::screen::{self, Output, WaylandScreen};
+use super::shm::ShmBuffer;
+use crate::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmScreen};
+use crate::config::{BarBuilder, DockDirection, ZIndex};
+use crate::draw::Rect;
+use crate::event;
+use crate::surface::Surface;
+
+/// The axis motion reported for one step of a mouse wheel by most
+/// compositors.
+const SCROLL_STEP: f64 = 10.0;
+
+#[derive(Debug, Default)]
+struct BarInfo {
+    /// The logical size the compositor configured.
+    size: (u32, u32),
+    /// The size asked for, used where the compositor leaves it to the client.
+    requested: (u32, u32),
+    /// The position in the compositor's coordinate space.
+    origin: (i32, i32),
+    /// The scale of the buffers, from the output the bar is on.
+    scale: u32,
+    configured: bool,
+    closed: bool,
+}
+
+impl BarInfo {
+    /// The size of the buffers, i.e. the size bars draw at.
+    fn buffer_size(&self) -> (u32, u32) {
+        let scale = self.scale.max(1);
+        (self.size.0 * scale, self.size.1 * scale)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    compositor: Option<wl_compositor::WlCompositor>,
+    shm: Option<wl_shm::WlShm>,
+    layer_shell: Option<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+    seat: Option<wl_seat::WlSeat>,
+    pointer: Option<wl_pointer::WlPointer>,
+    outputs: Vec<Output>,
+    bars: HashMap<event::BarId, BarInfo>,
+    events: VecDeque<event::Event>,
+    focus: Option<event::BarId>,
+    pointer_pos: (f64, f64),
+    scroll: (f64, f64),
+    // output changes are only reported once the initial state is known
+    initialized: bool,
+}
+
+impl State {
+    fn screens_changed(&mut self) {
+        if self.initialized && !matches!(self.events.back(), Some(event::Event::ScreensChanged)) {
+            self.events.push_back(event::Event::ScreensChanged);
+        }
+    }
+
+    fn motion(&self, bar: event::BarId, time: u32) -> event::MotionEvent {
+        let (origin, scale) = self
+            .bars
+            .get(&bar)
+            .map_or(((0, 0), 1), |info| (info.origin, info.scale.max(1)));
+        let (x, y) = self.pointer_pos;
+        // the bar draws in buffer pixels, while the root coordinates are the
+        // compositor's logical ones like the screen positions
+        event::MotionEvent {
+            bar,
+            x: (x * f64::from(scale)) as i32,
+            y: (y * f64::from(scale)) as i32,
+            root_x: origin.0 + x as i32,
+            root_y: origin.1 + y as i32,
+            // bars never get the keyboard focus, so the compositor doesn't
+            // tell them about modifiers
+            modifiers: 0,
+            time,
+        }
+    }
+
+    fn scroll(&mut self, bar: event::BarId, time: u32, axis: wl_pointer::Axis, value: f64) {
+        let (acc, directions) = match axis {
+            wl_pointer::Axis::VerticalScroll => (
+                &mut self.scroll.1,
+                (event::ScrollDirection::Up, event::ScrollDirection::Down),
+            ),
+            wl_pointer::Axis::HorizontalScroll => (
+                &mut self.scroll.0,
+                (event::ScrollDirection::Left, event::ScrollDirection::Right),
+            ),
+            _ => return,
+        };
+        // touchpads report small steps, so emit an event per wheel step
+        *acc += value;
+        let mut steps = Vec::new();
+        while acc.abs() >= SCROLL_STEP {
+            let up = *acc < 0.0;
+            *acc -= SCROLL_STEP.copysign(*acc);
+            steps.push(if up { directions.0 } else { directions.1 });
+        }
+        let motion = self.motion(bar, time);
+        for direction in steps {
+            self.events
+                .push_back(event::Event::Scroll(event::ScrollEvent {
+                    bar,
+                    x: motion.x,
+                    y: motion.y,
+                    root_x: motion.root_x,
+                    root_y: motion.root_y,
+                    direction,
+                    modifiers: motion.modifiers,
+                    time,
+                }));
+        }
+    }
+}
+
+impl Dispatch<wl_registry::WlRegistry, ()> for State {
+    fn event(
+        state: &mut Self,
+        registry: &wl_registry::WlRegistry,
+        event: wl_registry::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_registry::Event::Global {
+                name,
+                interface,
+                version,
+            } => match interface.as_str() {
+                "wl_compositor" => {
+                    state.compositor = Some(registry.bind(name, version.min(4), qh, ()));
+                }
+                "wl_shm" => state.shm = Some(registry.bind(name, 1, qh, ())),
+                "zwlr_layer_shell_v1" => {
+                    state.layer_shell = Some(registry.bind(name, version.min(4), qh, ()));
+                }
+                "wl_seat" if state.seat.is_none() => {
+                    state.seat = Some(registry.bind(name, version.min(5), qh, ()));
+                }
+                "wl_output" => {
+                    let output = registry.bind(name, version.min(4), qh, name);
+                    state.outputs.push(Output::new(name, output));
+                }
+                _ => {}
+            },
+            wl_registry::Event::GlobalRemove { name } => {
+                if let Some(i) = state.outputs.iter().position(|o| o.global == name) {
+                    state.outputs.remove(i).release();
+                    state.screens_changed();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, u32> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_output::WlOutput,
+        event: wl_output::Event,
+        global: &u32,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let changed = state
+            .outputs
+            .iter_mut()
+            .find(|o| o.global == *global)
+            .is_some_and(|o| o.handle_event(event));
+        if changed {
+            state.screens_changed();
+        }
+    }
+}
+
+impl Dispatch<wl_seat::WlSeat, ()> for State {
+    fn event(
+        state: &mut Self,
+        seat: &wl_seat::WlSeat,
+        event: wl_seat::Event,
+        _: &(),
+        _: &Connection,
+        qh: &QueueHandle<Self>,
+    ) {
+        if let wl_seat::Event::Capabilities {
+            capabilities: WEnum::Value(caps),
+        } = event
+        {
+            let has_pointer = caps.contains(wl_seat::Capability::Pointer);
+            if has_pointer && state.pointer.is_none() {
+                state.pointer = Some(seat.get_pointer(qh, ()));
+            } else if !has_pointer {
+                if let Some(pointer) = state.pointer.take() {
+                    if pointer.version() >= 3 {
+                        pointer.release();
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn map_button(button: u32) -> event::MouseButton {
+    // the evdev codes from linux/input-event-codes.h
+    match button {
+        0x110 => event::MouseButton::Left,
+        0x111 => event::MouseButton::Right,
+        0x112 => event::MouseButton::Middle,
+        0x113 => event::MouseButton::Back,
+        0x114 => event::MouseButton::Forward,
+        n => event::MouseButton::Other(n.wrapping_sub(0x110) as u8),
+    }
+}
+
+impl Dispatch<wl_pointer::WlPointer, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &wl_pointer::WlPointer,
+        event: wl_pointer::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            wl_pointer::Event::Enter {
+                surface,
+                surface_x,
+                surface_y,
+                ..
+            } => {
+                let bar = surface.id().protocol_id();
+                if state.bars.contains_key(&bar) {
+                    state.focus = Some(bar);
+                    state.pointer_pos = (surface_x, surface_y);
+                    state.scroll = (0.0, 0.0);
+                    let ev = state.motion(bar, 0);
+                    state.events.push_back(event::Event::PointerEnter(ev));
+                }
+            }
+            wl_pointer::Event::Leave { .. } => {
+                if let Some(bar) = state.focus.take() {
+                    let ev = state.motion(bar, 0);
+                    state.events.push_back(event::Event::PointerLeave(ev));
+                }
+            }
+            wl_pointer::Event::Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => {
+                if let Some(bar) = state.focus {
+                    state.pointer_pos = (surface_x, surface_y);
+                    let ev = state.motion(bar, time);
+                    state.events.push_back(event::Event::MouseMove(ev));
+                }
+            }
+            wl_pointer::Event::Button {
+                time,
+                button,
+                state: WEnum::Value(button_state),
+                ..
+            } => {
+                if let Some(bar) = state.focus {
+                    let motion = state.motion(bar, time);
+                    let pressed = button_state == wl_pointer::ButtonState::Pressed;
+                    let ev = event::ClickEvent {
+                        bar,
+                        x: motion.x,
+                        y: motion.y,
+                        root_x: motion.root_x,
+                        root_y: motion.root_y,
+                        button: map_button(button),
+                        pressed,
+                        modifiers: motion.modifiers,
+                        time,
+                    };
+                    state.events.push_back(if pressed {
+                        event::Event::MouseDown(ev)
+                    } else {
+                        event::Event::MouseUp(ev)
+                    });
+                }
+            }
+            wl_pointer::Event::Axis {
+                time,
+                axis: WEnum::Value(axis),
+                value,
+            } => {
+                if let Some(bar) = state.focus {
+                    state.scroll(bar, time, axis, value);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, event::BarId> for State {
+    fn event(
+        state: &mut Self,
+        layer: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        event: zwlr_layer_surface_v1::Event,
+        bar: &event::BarId,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let info = match state.bars.get_mut(bar) {
+            Some(info) => info,
+            None => return,
+        };
+        match event {
+            zwlr_layer_surface_v1::Event::Configure {
+                serial,
+                width,
+                height,
+            } => {
+                layer.ack_configure(serial);
+                let size = (
+                    if width == 0 { info.requested.0 } else { width },
+                    if height == 0 {
+                        info.requested.1
+                    } else {
+                        height
+                    },
+                );
+                let resized = info.configured && info.size != size;
+                info.size = size;
+                info.configured = true;
+                if resized {
+                    let (width, height) = info.buffer_size();
+                    state
+                        .events
+                        .push_back(event::Event::Resize(event::ResizeEvent {
+                            bar: *bar,
+                            width,
+                            height,
+                        }));
+                }
+            }
+            zwlr_layer_surface_v1::Event::Closed => info.closed = true,
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, Arc<AtomicBool>> for State {
+    fn event(
+        _: &mut Self,
+        _: &wl_buffer::WlBuffer,
+        event: wl_buffer::Event,
+        busy: &Arc<AtomicBool>,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        if let wl_buffer::Event::Release = event {
+            busy.store(false, Ordering::Release);
+        }
+    }
+}
+
+delegate_noop!(State: wl_compositor::WlCompositor);
+delegate_noop!(State: ignore wl_surface::WlSurface);
+delegate_noop!(State: ignore wl_shm::WlShm);
+delegate_noop!(State: wl_shm_pool::WlShmPool);
+delegate_noop!(State: zwlr_layer_shell_v1::ZwlrLayerShellV1);
+
+pub struct WaylandAdapter<B: Bar> {
+    con: Connection,
+    queue: RefCell<EventQueue<State>>,
+    qh: QueueHandle<State>,
+    state: RefCell<State>,
+    closed: Cell<bool>,
+    _b: core::marker::PhantomData<B>,
+}
+
+pub struct WaylandAdapterBar<'a, B: Bar> {
+    dis: &'a WaylandAdapter<B>,
+    surface: wl_surface::WlSurface,
+    layer: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+    id: event::BarId,
+    namespace: String,
+    screen: WaylandScreen,
+    docking: DockDirection,
+    z_index: ZIndex,
+    left: i32,
+    right: i32,
+    width: u32,
+    offset: u32,
+    transparent: bool,
+    contents: Surface,
+    buffers: Vec<ShmBuffer>,
+}
+
+impl<'a, B: Bar> WmAdapterGetBar<'a, B> for WaylandAdapter<B> {
+    type AdapterBar = WaylandAdapterBar<'a, B>;
+}
+
+impl<B: Bar> WmAdapterExt<B> for WaylandAdapter<B> {}
+
+fn is_would_block(e: &WaylandError) -> bool {
+    matches!(e, WaylandError::Io(e) if e.kind() == std::io::ErrorKind::WouldBlock)
+}
+
+fn socket_path(name: &str) -> Result<PathBuf, Error> {
+    let path = PathBuf::from(name);
+    if path.is_absolute() {
+        return Ok(path);
+    }
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(|dir| PathBuf::from(dir).join(path))
+        .ok_or_else(|| Error::Custom("XDG_RUNTIME_DIR is not set".to_owned()))
+}
+
+impl<B: Bar> WaylandAdapter<B> {
+    /// Connects to the compositor socket `name`, or to `$WAYLAND_DISPLAY` if
+    /// it is `None`. Relative names are looked up in `$XDG_RUNTIME_DIR`.
+    pub fn connect(name: Option<&str>) -> Result<Self, Error> {
+        let con = match name {
+            Some(name) => Connection::from_socket(UnixStream::connect(socket_path(name)?)?)?,
+            None => Connection::connect_to_env()?,
+        };
+        let mut queue = con.new_event_queue();
+        let qh = queue.handle();
+        con.display().get_registry(&qh, ());
+        let mut state = State::default();
+        // the first roundtrip announces the globals, the second the state of
+        // the outputs and seat bound in response
+        queue.roundtrip(&mut state)?;
+        queue.roundtrip(&mut state)?;
+        if state.compositor.is_none() || state.shm.is_none() {
+            return Err(Error::Custom(
+                "the compositor lacks wl_compositor or wl_shm".to_owned(),
+            ));
+        }
+        if state.layer_shell.is_none() {
+            return Err(Error::Custom(
+                "the compositor doesn't support wlr-layer-shell".to_owned(),
+            ));
+        }
+        state.initialized = true;
+        Ok(Self {
+            con,
+            queue: RefCell::new(queue),
+            qh,
+            state: RefCell::new(state),
+            closed: Cell::new(false),
+            _b: core::marker::PhantomData,
+        })
+    }
+
+    /// Dispatches the events that arrived, waiting for some if `blocking`
+    /// is set.
+    fn dispatch(&self, blocking: bool) -> Result<(), Error> {
+        let mut queue = self.queue.borrow_mut();
+        let mut state = self.state.borrow_mut();
+        queue.dispatch_pending(&mut state)?;
+        if !state.events.is_empty() {
+            return Ok(());
+        }
+        if blocking {
+            queue.blocking_dispatch(&mut state)?;
+            return Ok(());
+        }
+        // the caller is about to wait, so make sure all requests are sent
+        match queue.flush() {
+            Err(e) if !is_would_block(&e) => return Err(e.into()),
+            _ => {}
+        }
+        if let Some(guard) = queue.prepare_read() {
+            match guard.read() {
+                Err(e) if !is_would_block(&e) => return Err(e.into()),
+                _ => {}
+            }
+        }
+        queue.dispatch_pending(&mut state)?;
+        Ok(())
+    }
+
+    fn connection_lost(&self, e: Error) -> Result<event::Event, Error> {
+        match e {
+            Error::WaylandError(WaylandError::Io(_))
+            | Error::DispatchError(wayland_client::DispatchError::Backend(WaylandError::Io(_))) => {
+                self.closed.set(true);
+                Ok(event::Event::Quit)
+            }
+            e => Err(e),
+        }
+    }
+
+    fn pop_event(&self) -> Option<event::Event> {
+        self.state.borrow_mut().events.pop_front()
+    }
+
+    fn roundtrip(&self) -> Result<(), Error> {
+        self.queue
+            .borrow_mut()
+            .roundtrip(&mut self.state.borrow_mut())?;
+        Ok(())
+    }
+
+    fn flush(&self) -> Result<(), Error> {
+        match self.con.flush() {
+            Err(e) if !is_would_block(&e) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<B: Bar> WmAdapter<B> for WaylandAdapter<B> {
+    type Error = Error;
+    type Surface = Surface;
+    type Screen = WaylandScreen;
+    // layer surfaces are placed after the exclusive zones of the ones
+    // anchored to the same edge before them
+    const STACKS_EXCLUSIVE_ZONES: bool = true;
+
+    fn new(cfg: &BarBuilder) -> Result<Self, Self::Error> {
+        Self::connect(cfg.get_display())
+    }
+
+    fn get_screen_count(&self) -> usize {
+        screen::list_screens(&self.state.borrow().outputs).len()
+    }
+
+    fn get_screen(&self, n: usize) -> Option<WaylandScreen> {
+        screen::list_screens(&self.state.borrow().outputs)
+            .into_iter()
+            .nth(n)
+    }
+
+    fn await_event(&self) -> Result<event::Event, Self::Error> {
+        loop {
+            if let Some(ev) = self.pop_event() {
+                return Ok(ev);
+            }
+            if let Err(e) = self.dispatch(true) {
+                return self.connection_lost(e);
+            }
+        }
+    }
+
+    fn poll_event(&self) -> Result<Poll<event::Event>, Self::Error> {
+        if let Some(ev) = self.pop_event() {
+            return Ok(Poll::Ready(ev));
+        }
+        if let Err(e) = self.dispatch(false) {
+            return self.connection_lost(e).map(Poll::Ready);
+        }
+        Ok(self.pop_event().map_or(Poll::Pending, Poll::Ready))
+    }
+
+    fn get_fd(&self) -> RawFd {
+        self.con.backend().poll_fd().as_raw_fd()
+    }
+}
+
+fn layer(z_index: ZIndex) -> zwlr_layer_shell_v1::Layer {
+    match z_index {
+        ZIndex::AboveEverything => zwlr_layer_shell_v1::Layer::Overlay,
+        ZIndex::Normal => zwlr_layer_shell_v1::Layer::Top,
+        ZIndex::BelowEverything => zwlr_layer_shell_v1::Layer::Bottom,
+    }
+}
+
+impl<'a, B: Bar> WaylandAdapterBar<'a, B> {
+    fn create_layer(
+        dis: &WaylandAdapter<B>,
+        surface: &wl_surface::WlSurface,
+        screen: &WaylandScreen,
+        z_index: ZIndex,
+        namespace: &str,
+    ) -> Result<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, Error> {
+        let state = dis.state.borrow();
+        let layer_shell = state
+            .layer_shell
+            .as_ref()
+            .ok_or_else(|| Error::Custom("no layer shell".to_owned()))?;
+        let layer = layer_shell.get_layer_surface(
+            surface,
+            Some(&screen.output),
+            layer(z_index),
+            namespace.to_owned(),
+            &dis.qh,
+            surface.id().protocol_id(),
+        );
+        layer.set_keyboard_interactivity(zwlr_layer_surface_v1::KeyboardInteractivity::None);
+        Ok(layer)
+    }
+
+    /// Sends the geometry and waits for the compositor to configure the
+    /// surface if it wasn't yet.
+    fn reconfigure(&mut self) -> Result<(), Error> {
+        use zwlr_layer_surface_v1::Anchor;
+        let (x, y) = self.screen.position();
+        let (sw, sh) = self.screen.dimensions();
+        let (width, offset) = (self.width as i32, self.offset as i32);
+        let (left, right) = (self.left, self.right);
+        let length = |len: u32| (len as i32 - left - right).max(1) as u32;
+        let (anchor, size, margin, origin) = match self.docking {
+            DockDirection::Top => (
+                Anchor::Top | Anchor::Left | Anchor::Right,
+                (length(sw), self.width),
+                (offset, right, 0, left),
+                (x + left, y + offset),
+            ),
+            DockDirection::Bottom => (
+                Anchor::Bottom | Anchor::Left | Anchor::Right,
+                (length(sw), self.width),
+                (0, right, offset, left),
+                (x + left, y + sh as i32 - width - offset),
+            ),
+            DockDirection::Left => (
+                Anchor::Left | Anchor::Top | Anchor::Bottom,
+                (self.width, length(sh)),
+                (left, 0, right, offset),
+                (x + offset, y + left),
+            ),
+            DockDirection::Right => (
+                Anchor::Right | Anchor::Top | Anchor::Bottom,
+                (self.width, length(sh)),
+                (left, offset, right, 0),
+                (x + sw as i32 - width - offset, y + left),
+            ),
+        };
+        // a zero length stretches the bar between the anchored edges
+        let (w, h) = match self.docking {
+            DockDirection::Top | DockDirection::Bottom => (0, size.1),
+            DockDirection::Left | DockDirection::Right => (size.0, 0),
+        };
+        self.layer.set_anchor(anchor);
+        self.layer.set_size(w, h);
+        self.layer
+            .set_margin(margin.0, margin.1, margin.2, margin.3);
+        // compositors add the margin towards the edge to the exclusive zone,
+        // so this reserves the offset as well, like the X11 struts do
+        self.layer.set_exclusive_zone(width);
+        // the buffers are drawn at the output's resolution, so the compositor
+        // doesn't have to upscale them
+        let scale = if self.surface.version() >= 3 {
+            self.surface.set_buffer_scale(self.screen.scale() as i32);
+            self.screen.scale()
+        } else {
+            1
+        };
+        self.surface.commit();
+        let (configured, rescaled) = {
+            let mut state = self.dis.state.borrow_mut();
+            let info = state.bars.entry(self.id).or_default();
+            let rescaled = info.configured && info.scale != scale;
+            info.requested = size;
+            info.origin = origin;
+            info.scale = scale;
+            (info.configured, rescaled.then(|| info.buffer_size()))
+        };
+        if let Some((width, height)) = rescaled {
+            self.dis
+                .state
+                .borrow_mut()
+                .events
+                .push_back(event::Event::Resize(event::ResizeEvent {
+                    bar: self.id,
+                    width,
+                    height,
+                }));
+        }
+        if !configured {
+            self.await_configure()?;
+        }
+        self.dis.flush()
+    }
+
+    fn await_configure(&self) -> Result<(), Error> {
+        loop {
+            let state = self.dis.state.borrow();
+            match state.bars.get(&self.id) {
+                Some(info) if info.closed => {
+                    return Err(Error::Custom("the compositor closed the bar".to_owned()))
+                }
+                Some(info) if info.configured => return Ok(()),
+                _ => {}
+            }
+            drop(state);
+            self.dis.roundtrip()?;
+        }
+    }
+
+    /// Moves the surface to another output or layer. Layer surfaces can't
+    /// change their output, so the role object is created anew.
+    fn recreate_layer(&mut self) -> Result<(), Error> {
+        self.surface.attach(None, 0, 0);
+        self.surface.commit();
+        self.layer.destroy();
+        self.buffers.clear();
+        if let Some(info) = self.dis.state.borrow_mut().bars.get_mut(&self.id) {
+            info.configured = false;
+            info.closed = false;
+        }
+        self.layer = Self::create_layer(
+            self.dis,
+            &self.surface,
+            &self.screen,
+            self.z_index,
+            &self.namespace,
+        )?;
+        self.reconfigure()?;
+        // the new surface has no contents yet
+        let (width, height) = self.get_size_inner();
+        self.dis
+            .state
+            .borrow_mut()
+            .events
+            .push_back(event::Event::Expose(event::ExposeEvent {
+                bar: self.id,
+                rect: Rect::new(0, 0, width, height),
+            }));
+        Ok(())
+    }
+
+    fn get_size_inner(&self) -> (u32, u32) {
+        self.dis
+            .state
+            .borrow()
+            .bars
+            .get(&self.id)
+            .map_or((0, 0), BarInfo::buffer_size)
+    }
+
+    /// Copies `rect` of `surface` to `(x, y)` and shows the result.
+    fn present(&mut self, surface: &Surface, rect: Rect, x: i32, y: i32) -> Result<(), Error> {
+        let closed = self
+            .dis
+            .state
+            .borrow()
+            .bars
+            .get(&self.id)
+            .is_none_or(|info| info.closed || !info.configured);
+        if closed || self.dis.closed.get() {
+            return Ok(());
+        }
+        let size = self.get_size_inner();
+        if size.0 == 0 || size.1 == 0 {
+            return Ok(());
+        }
+        if (self.contents.width(), self.contents.height()) != size {
+            self.contents.resize(size.0, size.1);
+        }
+        self.contents.copy_rect(surface, rect, x, y);
+        // the compositor may still read the buffers it didn't release
+        self.buffers.retain(|b| b.size() == size);
+        let i = match self.buffers.iter().position(|b| !b.is_busy()) {
+            Some(i) => i,
+            None => {
+                let state = self.dis.state.borrow();
+                let shm = state
+                    .shm
+                    .as_ref()
+                    .ok_or_else(|| Error::Custom("no wl_shm".to_owned()))?;
+                let buffer = ShmBuffer::new(shm, size, self.transparent, &self.dis.qh)?;
+                self.buffers.push(buffer);
+                self.buffers.len() - 1
+            }
+        };
+        let buffer = &mut self.buffers[i];
+        buffer.write(&self.contents);
+        buffer.attach(&self.surface);
+        let damage = Rect::new(x, y, rect.width, rect.height);
+        if self.surface.version() >= 4 {
+            self.surface.damage_buffer(
+                damage.x,
+                damage.y,
+                damage.width as i32,
+                damage.height as i32,
+            );
+        } else {
+            // surface coordinates are logical, so round outwards
+            let scale = self
+                .dis
+                .state
+                .borrow()
+                .bars
+                .get(&self.id)
+                .map_or(1, |info| info.scale.max(1)) as i32;
+            let (x0, y0) = (damage.x.div_euclid(scale), damage.y.div_euclid(scale));
+            let x1 = (damage.x + damage.width as i32 + scale - 1).div_euclid(scale);
+            let y1 = (damage.y + damage.height as i32 + scale - 1).div_euclid(scale);
+            self.surface.damage(x0, y0, x1 - x0, y1 - y0);
+        }
+        self.surface.commit();
+        self.dis.flush()
+    }
+}
+
+impl<'a, B: Bar> WmAdapterBar<'a, B, WaylandAdapter<B>> for WaylandAdapterBar<'a, B> {
+    fn new(
+        _bar: &B,
+        wm: &'a WaylandAdapter<B>,
+        cfg: &BarBuilder,
+        screen: &WaylandScreen,
+    ) -> Result<Self, Error> {
+        if *cfg.get_width() == 0 {
+            return Err(Error::Custom("invalid bar width (0)".to_owned()));
+        }
+        let surface = {
+            let state = wm.state.borrow();
+            let compositor = state
+                .compositor
+                .as_ref()
+                .ok_or_else(|| Error::Custom("no wl_compositor".to_owned()))?;
+            compositor.create_surface(&wm.qh, ())
+        };
+        let id = surface.id().protocol_id();
+        let namespace = cfg.get_class().to_owned();
+        let layer = Self::create_layer(wm, &surface, screen, *cfg.get_z_index(), &namespace)?;
+        let mut slf = Self {
+            dis: wm,
+            surface,
+            layer,
+            id,
+            namespace,
+            screen: screen.clone(),
+            docking: *cfg.get_docking(),
+            z_index: *cfg.get_z_index(),
+            left: *cfg.get_margin_left(),
+            right: *cfg.get_margin_right(),
+            width: *cfg.get_width(),
+            offset: *cfg.get_offset(),
+            transparent: *cfg.get_transparency(),
+            contents: Surface::new(0, 0),
+            buffers: Vec::new(),
+        };
+        wm.state.borrow_mut().bars.insert(id, BarInfo::default());
+        slf.reconfigure()?;
+        Ok(slf)
+    }
+
+    fn set_docking(&mut self, dir: DockDirection) -> Result<(), Error> {
+        self.docking = dir;
+        self.reconfigure()
+    }
+
+    fn get_docking(&self) -> DockDirection {
+        self.docking
+    }
+
+    fn set_title(&mut self, _title: &str) -> Result<(), Error> {
+        // layer surfaces have no title, only the namespace set on creation
+        Ok(())
+    }
+
+    fn set_z_index(&mut self, z_index: ZIndex) -> Result<(), Error> {
+        self.z_index = z_index;
+        if self.layer.version() >= 2 {
+            self.layer.set_layer(layer(z_index));
+            self.surface.commit();
+            self.dis.flush()
+        } else {
+            self.recreate_layer()
+        }
+    }
+
+    fn set_screen(&mut self, screen: &WaylandScreen) -> Result<(), Error> {
+        let moved = screen.output != self.screen.output;
+        self.screen = screen.clone();
+        if moved {
+            self.recreate_layer()
+        } else {
+            self.reconfigure()
+        }
+    }
+
+    fn set_margin(&mut self, left: i32, right: i32) -> Result<(), Error> {
+        self.left = left;
+        self.right = right;
+        self.reconfigure()
+    }
+
+    fn set_thickness(&mut self, thickness: u32) -> Result<(), Error> {
+        if thickness == 0 {
+            return Err(Error::Custom("invalid bar width (0)".to_owned()));
+        }
+        self.width = thickness;
+        self.reconfigure()
+    }
+
+    fn set_offset(&mut self, offset: u32) -> Result<(), Error> {
+        self.offset = offset;
+        self.reconfigure()
+    }
+
+    fn blit(&mut self, surface: &Surface, x: i32, y: i32) -> Result<(), Error> {
+        let rect = Rect::new(0, 0, surface.width(), surface.height());
+        self.present(surface, rect, x, y)
+    }
+
+    fn blit_rect(&mut self, surface: &Surface, rect: Rect) -> Result<(), Error> {
+        self.present(surface, rect, rect.x, rect.y)
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        self.get_size_inner()
+    }
+
+    fn get_id(&self) -> event::BarId {
+        self.id
+    }
+
+    fn destroy(self) -> Result<(), Error> {
+        let mut state = self.dis.state.borrow_mut();
+        state.bars.remove(&self.id);
+        if state.focus == Some(self.id) {
+            state.focus = None;
+        }
+        drop(state);
+        if self.dis.closed.get() {
+            return Ok(());
+        }
+        self.layer.destroy();
+        self.surface.destroy();
+        drop(self.buffers);
+        self.dis.flush()
+    }
+}
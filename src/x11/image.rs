@@ -0,0 +1,92 @@
+use x11rb::protocol::xproto;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PixelFormat {
+    pub depth: u8,
+    bits_per_pixel: u8,
+    scanline_pad: u8,
+    msb_first: bool,
+    red: (u32, u32),
+    green: (u32, u32),
+    blue: (u32, u32),
+    alpha: Option<(u32, u32)>,
+}
+
+fn mask_shift(mask: u32) -> (u32, u32) {
+    if mask == 0 {
+        (0, 0)
+    } else {
+        (mask, mask.trailing_zeros())
+    }
+}
+
+fn scale_channel(value: u32, (mask, shift): (u32, u32)) -> u32 {
+    let max = mask >> shift;
+    ((value * max + 127) / 255) << shift
+}
+
+impl PixelFormat {
+    pub fn new(setup: &xproto::Setup, depth: u8, visual: &xproto::Visualtype) -> Option<Self> {
+        let format = setup.pixmap_formats.iter().find(|f| f.depth == depth)?;
+        let color_mask = visual.red_mask | visual.green_mask | visual.blue_mask;
+        let depth_mask = if depth >= 32 { !0 } else { (1u32 << depth) - 1 };
+        let alpha_mask = depth_mask & !color_mask;
+        Some(Self {
+            depth,
+            bits_per_pixel: format.bits_per_pixel,
+            scanline_pad: format.scanline_pad,
+            msb_first: setup.image_byte_order == xproto::ImageOrder::MSBFirst,
+            red: mask_shift(visual.red_mask),
+            green: mask_shift(visual.green_mask),
+            blue: mask_shift(visual.blue_mask),
+            alpha: if depth == 32 && alpha_mask != 0 {
+                Some(mask_shift(alpha_mask))
+            } else {
+                None
+            },
+        })
+    }
+
+    pub fn stride(&self, width: u16) -> usize {
+        let pad = self.scanline_pad.max(8) as usize;
+        let bits = width as usize * self.bits_per_pixel as usize;
+        bits.div_ceil(pad) * pad / 8
+    }
+
+    fn is_native_argb(&self) -> bool {
+        self.bits_per_pixel == 32
+            && self.msb_first == cfg!(target_endian = "big")
+            && self.red == (0xff0000, 16)
+            && self.green == (0xff00, 8)
+            && self.blue == (0xff, 0)
+            && self.alpha.is_none_or(|a| a == (0xff000000, 24))
+    }
+
+    fn encode_pixel(&self, argb: u32) -> u32 {
+        let channel = |shift: u32| (argb >> shift) & 0xff;
+        let mut px = scale_channel(channel(16), self.red)
+            | scale_channel(channel(8), self.green)
+            | scale_channel(channel(0), self.blue);
+        if let Some(alpha) = self.alpha {
+            px |= scale_channel(channel(24), alpha);
+        }
+        px
+    }
+
+    /// Writes one scanline of `src` in the server's ZPixmap layout into `out`,
+    /// which must be at least `stride(src.len())` bytes long.
+    pub fn encode_row(&self, src: &[u32], out: &mut [u8]) {
+        if self.is_native_argb() {
+            out[..src.len() << 2].copy_from_slice(super::wm::serialize_u32(src));
+        } else {
+            let bytes = (self.bits_per_pixel as usize).div_ceil(8);
+            for (&argb, dst) in src.iter().zip(out.chunks_exact_mut(bytes)) {
+                let px = self.encode_pixel(argb);
+                for (i, b) in dst.iter_mut().enumerate() {
+                    let i = if self.msb_first { bytes - 1 - i } else { i };
+                    *b = (px >> (i * 8)) as u8;
+                }
+            }
+        }
+    }
+}
@@ -1,6 +1,11 @@
 mod error;
+mod image;
+mod screen;
+mod shm;
 mod wm;
 
+#[doc(inline)]
+pub use screen::X11Screen;
 #[doc(inline)]
 pub use wm::*;
 
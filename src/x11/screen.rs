@@ -0,0 +1,186 @@
+use super::error::Error;
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xinerama::ConnectionExt as _;
+use x11rb::protocol::xproto::{self, ConnectionExt as _};
+
+use crate::bar::WmScreen;
+
+/// A monitor, i.e. the part of an X screen a bar is placed on.
+///
+/// Monitors are taken from RandR (1.5 or later) or Xinerama. Without either
+/// extension every X screen is reported as a single monitor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct X11Screen {
+    pub(crate) screen: usize,
+    pub(crate) root: xproto::Window,
+    pub(crate) root_size: (u16, u16),
+    pub(crate) pos: (i16, i16),
+    pub(crate) size: (u16, u16),
+    physical_size: Option<(u32, u32)>,
+    name: Option<String>,
+    primary: bool,
+}
+
+impl X11Screen {
+    fn whole(n: usize, screen: &xproto::Screen, root_size: (u16, u16), primary: bool) -> Self {
+        Self {
+            screen: n,
+            root: screen.root,
+            root_size,
+            pos: (0, 0),
+            size: root_size,
+            physical_size: Some((
+                screen.width_in_millimeters.into(),
+                screen.height_in_millimeters.into(),
+            )),
+            name: None,
+            primary,
+        }
+    }
+
+    /// The index of the X screen the monitor belongs to.
+    pub fn get_screen_number(&self) -> usize {
+        self.screen
+    }
+}
+
+impl WmScreen for X11Screen {
+    fn dimensions(&self) -> (u32, u32) {
+        (self.size.0.into(), self.size.1.into())
+    }
+    fn physical_dimensions(&self) -> Option<(f32, f32)> {
+        self.physical_size.map(|(w, h)| (w as f32, h as f32))
+    }
+    fn position(&self) -> (i32, i32) {
+        (self.pos.0.into(), self.pos.1.into())
+    }
+    fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+    fn is_primary(&self) -> bool {
+        self.primary
+    }
+}
+
+fn has_randr_monitors<C: Connection>(con: &C) -> bool {
+    con.randr_query_version(1, 5)
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some_and(|v| (v.major_version, v.minor_version) >= (1, 5))
+}
+
+/// Subscribes to monitor configuration changes on all roots. Returns whether
+/// change notifications will be delivered.
+pub(crate) fn select_changes<C: Connection>(con: &C) -> Result<bool, Error> {
+    if !has_randr_monitors(con) {
+        return Ok(false);
+    }
+    let mask = randr::NotifyMask::ScreenChange
+        | randr::NotifyMask::CrtcChange
+        | randr::NotifyMask::OutputChange;
+    let cookies = con
+        .setup()
+        .roots
+        .iter()
+        .map(|screen| con.randr_select_input(screen.root, mask))
+        .collect::<Result<Vec<_>, _>>()?;
+    for cookie in cookies {
+        cookie.check()?;
+    }
+    Ok(true)
+}
+
+fn is_xinerama_active<C: Connection>(con: &C) -> bool {
+    con.xinerama_is_active()
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some_and(|v| v.state != 0)
+}
+
+fn query_randr<C: Connection>(
+    con: &C,
+    n: usize,
+    screen: &xproto::Screen,
+    root_size: (u16, u16),
+) -> Result<Vec<X11Screen>, Error> {
+    let monitors = con.randr_get_monitors(screen.root, true)?.reply()?.monitors;
+    let names = monitors
+        .iter()
+        .map(|m| con.get_atom_name(m.name))
+        .collect::<Result<Vec<_>, _>>()?;
+    monitors
+        .into_iter()
+        .zip(names)
+        .map(|(m, name)| {
+            let name = name.reply()?.name;
+            Ok(X11Screen {
+                pos: (m.x, m.y),
+                size: (m.width, m.height),
+                physical_size: Some((m.width_in_millimeters, m.height_in_millimeters))
+                    .filter(|&(w, h)| w != 0 && h != 0),
+                name: Some(String::from_utf8_lossy(&name).into_owned()),
+                primary: m.primary,
+                ..X11Screen::whole(n, screen, root_size, false)
+            })
+        })
+        .collect()
+}
+
+fn query_xinerama<C: Connection>(
+    con: &C,
+    n: usize,
+    screen: &xproto::Screen,
+    root_size: (u16, u16),
+) -> Result<Vec<X11Screen>, Error> {
+    let infos = con.xinerama_query_screens()?.reply()?.screen_info;
+    Ok(infos
+        .into_iter()
+        .enumerate()
+        .map(|(i, info)| X11Screen {
+            pos: (info.x_org, info.y_org),
+            size: (info.width, info.height),
+            physical_size: None,
+            ..X11Screen::whole(n, screen, root_size, i == 0)
+        })
+        .collect())
+}
+
+/// Lists the monitors of all X screens. `preferred` is the screen number
+/// returned when connecting; only its primary monitor, or else its first
+/// one, is reported as primary.
+pub(crate) fn query_screens<C: Connection>(
+    con: &C,
+    preferred: usize,
+) -> Result<Vec<X11Screen>, Error> {
+    let randr = has_randr_monitors(con);
+    // Xinerama merges all X screens into one, so it can only describe the first
+    let xinerama = !randr && con.setup().roots.len() == 1 && is_xinerama_active(con);
+    let mut screens = Vec::new();
+    for (n, screen) in con.setup().roots.iter().enumerate() {
+        // the sizes in the setup are outdated once the screen was resized
+        let geometry = con.get_geometry(screen.root)?.reply()?;
+        let root_size = (geometry.width, geometry.height);
+        let monitors = if randr {
+            query_randr(con, n, screen, root_size)?
+        } else if xinerama {
+            query_xinerama(con, n, screen, root_size)?
+        } else {
+            Vec::new()
+        };
+        if monitors.is_empty() {
+            screens.push(X11Screen::whole(n, screen, root_size, false));
+        } else {
+            screens.extend(monitors);
+        }
+    }
+    // RandR marks a primary monitor on every X screen
+    let primary = screens
+        .iter()
+        .position(|s| s.screen == preferred && s.primary)
+        .or_else(|| screens.iter().position(|s| s.screen == preferred));
+    for (i, s) in screens.iter_mut().enumerate() {
+        s.primary = Some(i) == primary;
+    }
+    Ok(screens)
+}
@@ -0,0 +1,90 @@
+use super::error::Error;
+use x11rb::connection::Connection;
+use x11rb::errors::ReplyError;
+use x11rb::protocol::shm::{self, ConnectionExt};
+
+#[derive(Debug)]
+pub(crate) struct ShmSegment<'a, C: Connection> {
+    con: &'a C,
+    seg: shm::Seg,
+    // whether the server still has the segment attached
+    attached: bool,
+    addr: *mut u8,
+    size: usize,
+}
+
+pub(crate) fn is_supported<C: Connection>(con: &C) -> bool {
+    con.shm_query_version()
+        .ok()
+        .and_then(|cookie| cookie.reply().ok())
+        .is_some()
+}
+
+impl<'a, C: Connection> ShmSegment<'a, C> {
+    /// Creates a segment and attaches it to the server.
+    ///
+    /// Returns `Ok(None)` if either side refuses the segment, e.g. because
+    /// the server runs on a different host.
+    pub fn new(con: &'a C, size: usize) -> Result<Option<Self>, Error> {
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, size, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return Ok(None);
+        }
+        let addr = unsafe { libc::shmat(id, core::ptr::null(), 0) };
+        if addr as isize == -1 {
+            unsafe { libc::shmctl(id, libc::IPC_RMID, core::ptr::null_mut()) };
+            return Ok(None);
+        }
+        // the segment is only detached locally until the server attached it
+        let mut slf = Self {
+            con,
+            seg: x11rb::NONE,
+            attached: false,
+            addr: addr as *mut u8,
+            size,
+        };
+        slf.seg = con.generate_id()?;
+        let attached = con.shm_attach(slf.seg, id as u32, true)?.check();
+        // both sides have attached, so the segment goes away with the last
+        // detach
+        unsafe { libc::shmctl(id, libc::IPC_RMID, core::ptr::null_mut()) };
+        slf.attached = match attached {
+            Ok(()) => true,
+            Err(ReplyError::X11Error(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Some(slf))
+    }
+
+    pub fn seg(&self) -> shm::Seg {
+        self.seg
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.addr, self.size) }
+    }
+
+    pub fn destroy(mut self) -> Result<(), Error> {
+        self.detach()
+    }
+
+    fn detach(&mut self) -> Result<(), Error> {
+        if self.attached {
+            self.attached = false;
+            self.con.shm_detach(self.seg)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, C: Connection> Drop for ShmSegment<'a, C> {
+    fn drop(&mut self) {
+        // the server would keep the segment alive until the connection closes
+        let _ = self.detach();
+        unsafe { libc::shmdt(self.addr as *const libc::c_void) };
+    }
+}
@@ -1,13 +1,22 @@
 pub use super::error::Error;
+use core::cell::{Cell, RefCell};
 use core::convert::TryInto;
 use core::task::Poll;
+use std::collections::{HashMap, VecDeque};
+use std::os::unix::io::{AsRawFd, RawFd};
+use x11::protocol::shm::ConnectionExt as _;
 use x11::protocol::xproto;
 use x11rb as x11;
 use xproto::ConnectionExt;
 
-use crate::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmScreen};
-use crate::config::{BarBuilder, DockDirection};
+use super::image::PixelFormat;
+use super::screen::{self, X11Screen};
+use super::shm::{self, ShmSegment};
+use crate::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar};
+use crate::config::{BarBuilder, DockDirection, DockGeometry, ZIndex};
+use crate::draw::Rect;
 use crate::event;
+use crate::surface::Surface;
 
 const fn _assert_u32_u8_align() -> bool {
     core::mem::align_of::<u32>() == core::mem::align_of::<u8>() * 4
@@ -15,7 +24,9 @@ const fn _assert_u32_u8_align() -> bool {
 }
 const _ASSERT_U32_U8_ALIGN: u32 = [0][1 - (_assert_u32_u8_align() as usize)];
 
-fn serialize_u32(arr: &[u32]) -> &[u8] {
+const PUT_IMAGE_HEADER: usize = 24;
+
+pub(crate) fn serialize_u32(arr: &[u32]) -> &[u8] {
     unsafe { core::slice::from_raw_parts(arr.as_ptr() as *const u8, arr.len() << 2) }
 }
 
@@ -29,23 +40,33 @@ x11::atom_manager! {
         _NET_WM_WINDOW_TYPE_DOCK,
         _NET_WM_STATE,
         _NET_WM_STATE_SKIP_TASKBAR,
+        _NET_WM_STATE_ABOVE,
         _NET_WM_STATE_BELOW,
         _NET_WM_STATE_STICKY,
         _NET_WM_STRUT,
         _NET_WM_STRUT_PARTIAL,
         _NET_WM_DESKTOP,
         _NET_WM_ALLOWED_ACTIONS,
+        _NET_WM_NAME,
+        _NET_WM_PID,
+        UTF8_STRING,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
     }
 }
 
 pub trait X11Connection: x11rb::connection::Connection + Sized + 'static {
     fn connect(dpy_name: Option<&str>) -> Result<(Self, usize), Error>;
+    fn get_fd(&self) -> RawFd;
 }
 
 impl X11Connection for x11rb::rust_connection::RustConnection {
     fn connect(dpy_name: Option<&str>) -> Result<(Self, usize), Error> {
         Ok(x11rb::rust_connection::RustConnection::connect(dpy_name)?)
     }
+    fn get_fd(&self) -> RawFd {
+        self.stream().as_raw_fd()
+    }
 }
 
 #[cfg(feature = "wm-x11-xcb")]
@@ -58,25 +79,53 @@ impl X11Connection for x11rb::xcb_ffi::XCBConnection {
         let dpy_name = dpy_name.as_deref();
         Ok(x11::xcb_ffi::XCBConnection::connect(dpy_name)?)
     }
+    fn get_fd(&self) -> RawFd {
+        self.as_raw_fd()
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct X11Adapter<B: Bar, C: X11Connection> {
     con: C,
     atoms: Atoms,
+    shm: bool,
+    preferred_screen: usize,
+    screens: RefCell<Vec<X11Screen>>,
+    // the bar sizes last reported by the server
+    sizes: RefCell<HashMap<xproto::Window, (u16, u16)>>,
+    // the shared memory segments the server may still read from, with the
+    // sequence number of the put request
+    shm_busy: RefCell<HashMap<x11::protocol::shm::Seg, u16>>,
+    // events read while waiting for a segment
+    deferred: RefCell<VecDeque<x11::protocol::Event>>,
+    closed: Cell<bool>,
     _b: core::marker::PhantomData<B>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
+enum ShmState<'a, C: X11Connection> {
+    Unavailable,
+    Detached,
+    Attached(ShmSegment<'a, C>),
+}
+
+#[derive(Debug)]
 pub struct X11AdapterBar<'a, B: Bar, C: X11Connection> {
     dis: &'a X11Adapter<B, C>,
+    root: xproto::Window,
     win: xproto::Window,
+    gc: xproto::Gcontext,
+    format: PixelFormat,
+    shm: ShmState<'a, C>,
+    docking: DockDirection,
+    z_index: ZIndex,
     left: i16,
     right: i16,
     pos: (i16, i16),
     screen_size: (u16, u16),
-    size: (u16, u16),
+    root_size: (u16, u16),
     width: u16,
+    offset: u16,
 }
 
 impl<'a, B: Bar, C: X11Connection> WmAdapterGetBar<'a, B> for X11Adapter<B, C> {
@@ -85,79 +134,320 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterGetBar<'a, B> for X11Adapter<B, C> {
 
 impl<B: Bar, C: X11Connection> WmAdapterExt<B> for X11Adapter<B, C> {}
 
-pub struct Surface;
+fn map_modifiers(state: u16) -> event::Modifiers {
+    [
+        (xproto::KeyButMask::Shift, event::MOD_SHIFT),
+        (xproto::KeyButMask::Control, event::MOD_CTRL),
+        (xproto::KeyButMask::Mod1, event::MOD_ALT),
+        (xproto::KeyButMask::Mod4, event::MOD_SUPER),
+    ]
+    .iter()
+    .filter(|(mask, _)| state & u16::from(*mask) != 0)
+    .fold(0, |mods, (_, m)| mods | m)
+}
+
+fn map_button_event(ev: xproto::ButtonPressEvent, pressed: bool) -> Option<event::Event> {
+    let (bar, x, y, root_x, root_y) = (
+        ev.event,
+        ev.event_x.into(),
+        ev.event_y.into(),
+        ev.root_x.into(),
+        ev.root_y.into(),
+    );
+    let (modifiers, time) = (map_modifiers(ev.state), ev.time);
+    let scroll = |direction| {
+        // every wheel step is reported as press followed by release
+        if pressed {
+            Some(event::Event::Scroll(event::ScrollEvent {
+                bar,
+                x,
+                y,
+                root_x,
+                root_y,
+                direction,
+                modifiers,
+                time,
+            }))
+        } else {
+            None
+        }
+    };
+    let button = match ev.detail {
+        1 => event::MouseButton::Left,
+        2 => event::MouseButton::Middle,
+        3 => event::MouseButton::Right,
+        4 => return scroll(event::ScrollDirection::Up),
+        5 => return scroll(event::ScrollDirection::Down),
+        6 => return scroll(event::ScrollDirection::Left),
+        7 => return scroll(event::ScrollDirection::Right),
+        8 => event::MouseButton::Back,
+        9 => event::MouseButton::Forward,
+        n => event::MouseButton::Other(n),
+    };
+    let ev = event::ClickEvent {
+        bar,
+        x,
+        y,
+        root_x,
+        root_y,
+        button,
+        pressed,
+        modifiers,
+        time,
+    };
+    Some(if pressed {
+        event::Event::MouseDown(ev)
+    } else {
+        event::Event::MouseUp(ev)
+    })
+}
 
-impl WmScreen for xproto::Screen {
-    fn dimensions(&self) -> (u32, u32) {
-        (self.width_in_pixels.into(), self.height_in_pixels.into())
+fn map_motion_event(ev: xproto::MotionNotifyEvent) -> event::MotionEvent {
+    event::MotionEvent {
+        bar: ev.event,
+        x: ev.event_x.into(),
+        y: ev.event_y.into(),
+        root_x: ev.root_x.into(),
+        root_y: ev.root_y.into(),
+        modifiers: map_modifiers(ev.state),
+        time: ev.time,
     }
-    fn physical_dimensions(&self) -> Option<(f32, f32)> {
-        Some((
-            self.width_in_millimeters.into(),
-            self.height_in_millimeters.into(),
-        ))
+}
+
+fn map_crossing_event(ev: xproto::EnterNotifyEvent) -> event::MotionEvent {
+    event::MotionEvent {
+        bar: ev.event,
+        x: ev.event_x.into(),
+        y: ev.event_y.into(),
+        root_x: ev.root_x.into(),
+        root_y: ev.root_y.into(),
+        modifiers: map_modifiers(ev.state),
+        time: ev.time,
     }
 }
 
 impl<B: Bar, C: X11Connection> X11Adapter<B, C> {
-    fn map_event(&self, ev: x11::protocol::Event) -> Option<event::Event> {
+    /// Connects to the display `dpy_name`, or to `$DISPLAY` if it is `None`.
+    pub fn connect(dpy_name: Option<&str>) -> Result<Self, Error> {
+        let (con, preferred) = C::connect(dpy_name)?;
+        let atoms = Atoms::new(&con)?.reply()?;
+        let shm = shm::is_supported(&con);
+        screen::select_changes(&con)?;
+        let screens = screen::query_screens(&con, preferred)?;
+        Ok(Self {
+            con,
+            atoms,
+            shm,
+            preferred_screen: preferred,
+            screens: RefCell::new(screens),
+            sizes: RefCell::new(HashMap::new()),
+            shm_busy: RefCell::new(HashMap::new()),
+            deferred: RefCell::new(VecDeque::new()),
+            closed: Cell::new(false),
+            _b: core::marker::PhantomData,
+        })
+    }
+
+    /// The number of the X screen named by the display, e.g. 1 for `":0.1"`.
+    pub fn get_screen_number(&self) -> usize {
+        self.preferred_screen
+    }
+
+    /// Marks the segment `ev` completes as free again. Returns the error of
+    /// the put request if `ev` is one.
+    fn complete_shm(&self, ev: &x11::protocol::Event) -> Option<Result<(), Error>> {
+        let mut busy = self.shm_busy.borrow_mut();
+        match ev {
+            x11::protocol::Event::ShmCompletion(ev) => busy.remove(&ev.shmseg).map(|_| Ok(())),
+            x11::protocol::Event::Error(e) => {
+                let seg = *busy.iter().find(|&(_, &seq)| seq == e.sequence)?.0;
+                busy.remove(&seg);
+                Some(Err(x11::errors::ReplyError::X11Error(*e).into()))
+            }
+            _ => None,
+        }
+    }
+
+    /// Waits until the server is done reading `seg`, keeping the other
+    /// events for `await_event` and `poll_event`.
+    fn await_shm(&self, seg: x11::protocol::shm::Seg) -> Result<(), Error> {
+        if !self.shm_busy.borrow().contains_key(&seg) {
+            return Ok(());
+        }
+        self.con.flush()?;
+        while self.shm_busy.borrow().contains_key(&seg) {
+            let ev = self.con.wait_for_event()?;
+            match self.complete_shm(&ev) {
+                Some(result) => result?,
+                None => self.deferred.borrow_mut().push_back(ev),
+            }
+        }
+        Ok(())
+    }
+
+    fn next_deferred(&self) -> Option<x11::protocol::Event> {
+        self.deferred.borrow_mut().pop_front()
+    }
+
+    fn map_event(&self, ev: x11::protocol::Event) -> Result<Option<event::Event>, Error> {
         use x11::protocol::Event::*;
-        Some(match ev {
-            ButtonPress(ev) => event::Event::MouseDown(event::ClickEvent {
-                x: ev.root_x.into(),
-                y: ev.root_y.into(),
-            }),
-            ButtonRelease(ev) => event::Event::MouseUp(event::ClickEvent {
-                x: ev.root_x.into(),
-                y: ev.root_y.into(),
-            }),
-            _ => return (None, dbg!(ev)).0,
+        if let Some(result) = self.complete_shm(&ev) {
+            return result.map(|()| None);
+        }
+        Ok(match ev {
+            ClientMessage(ev)
+                if ev.type_ == self.atoms.WM_PROTOCOLS
+                    && ev.format == 32
+                    && ev.data.as_data32()[0] == self.atoms.WM_DELETE_WINDOW =>
+            {
+                Some(event::Event::Quit)
+            }
+            ButtonPress(ev) => map_button_event(ev, true),
+            ButtonRelease(ev) => map_button_event(ev, false),
+            MotionNotify(ev) => Some(event::Event::MouseMove(map_motion_event(ev))),
+            EnterNotify(ev) => Some(event::Event::PointerEnter(map_crossing_event(ev))),
+            LeaveNotify(ev) => Some(event::Event::PointerLeave(map_crossing_event(ev))),
+            RandrScreenChangeNotify(_) | RandrNotify(_) => self.refresh_screens()?,
+            Expose(ev) => Some(event::Event::Expose(event::ExposeEvent {
+                bar: ev.window,
+                rect: Rect::new(ev.x.into(), ev.y.into(), ev.width.into(), ev.height.into()),
+            })),
+            ConfigureNotify(ev) => self.update_size(ev.window, (ev.width, ev.height)),
+            // the other structure notifications are only selected along with
+            // ConfigureNotify
+            _ => None,
         })
     }
+
+    fn update_size(&self, win: xproto::Window, size: (u16, u16)) -> Option<event::Event> {
+        let mut sizes = self.sizes.borrow_mut();
+        let known = sizes.get_mut(&win)?;
+        if *known == size {
+            return None;
+        }
+        *known = size;
+        Some(event::Event::Resize(event::ResizeEvent {
+            bar: win,
+            width: size.0.into(),
+            height: size.1.into(),
+        }))
+    }
+
+    /// Re-queries the monitors, reporting a change only if they differ from
+    /// the known ones. A single reconfiguration emits a burst of
+    /// notifications, most of which are dropped here.
+    fn refresh_screens(&self) -> Result<Option<event::Event>, Error> {
+        let screens = screen::query_screens(&self.con, self.preferred_screen)?;
+        if *self.screens.borrow() == screens {
+            return Ok(None);
+        }
+        *self.screens.borrow_mut() = screens;
+        Ok(Some(event::Event::ScreensChanged))
+    }
+
+    fn connection_lost(&self, e: x11::errors::ConnectionError) -> Result<event::Event, Error> {
+        match e {
+            x11::errors::ConnectionError::IOError(_) => {
+                self.closed.set(true);
+                Ok(event::Event::Quit)
+            }
+            e => Err(e.into()),
+        }
+    }
 }
 
 impl<B: Bar, C: X11Connection> WmAdapter<B> for X11Adapter<B, C> {
     type Error = Error;
     type Surface = Surface;
-    type Screen = xproto::Screen;
+    type Screen = X11Screen;
 
-    fn new(_cfg: &BarBuilder) -> Result<Self, Self::Error> {
-        let (con, _screen_count) = C::connect(None)?;
-        let atoms = Atoms::new(&con)?.reply()?;
-        Ok(Self {
-            con,
-            atoms,
-            _b: core::marker::PhantomData,
-        })
+    fn new(cfg: &BarBuilder) -> Result<Self, Self::Error> {
+        Self::connect(cfg.get_display())
     }
 
     fn get_screen_count(&self) -> usize {
-        self.con.setup().roots_len().into()
+        self.screens.borrow().len()
     }
 
-    fn get_screen(&self, n: usize) -> Option<&xproto::Screen> {
-        self.con.setup().roots.get(n)
+    fn get_screen(&self, n: usize) -> Option<X11Screen> {
+        self.screens.borrow().get(n).cloned()
     }
 
     fn await_event(&self) -> Result<event::Event, Self::Error> {
         loop {
-            return Ok(match self.map_event(self.con.wait_for_event()?) {
-                Some(v) => v,
-                _ => continue,
-            });
+            let ev = match self
+                .next_deferred()
+                .map_or_else(|| self.con.wait_for_event(), Ok)
+            {
+                Ok(ev) => ev,
+                Err(e) => return self.connection_lost(e),
+            };
+            if let Some(ev) = self.map_event(ev)? {
+                return Ok(ev);
+            }
         }
     }
 
     fn poll_event(&self) -> Result<Poll<event::Event>, Self::Error> {
-        Ok(
-            match self.con.poll_for_event()?.and_then(|v| self.map_event(v)) {
-                Some(v) => Poll::Ready(v),
-                None => Poll::Pending,
-            },
-        )
+        loop {
+            let ev = self
+                .next_deferred()
+                .map_or_else(|| self.con.poll_for_event(), |ev| Ok(Some(ev)));
+            match ev {
+                Ok(Some(ev)) => {
+                    if let Some(ev) = self.map_event(ev)? {
+                        return Ok(Poll::Ready(ev));
+                    }
+                }
+                // the caller is about to wait, so make sure all requests are sent
+                Ok(None) => {
+                    return match self.con.flush() {
+                        Ok(()) => Ok(Poll::Pending),
+                        Err(e) => self.connection_lost(e).map(Poll::Ready),
+                    }
+                }
+                Err(e) => return self.connection_lost(e).map(Poll::Ready),
+            }
+        }
+    }
+
+    fn get_fd(&self) -> RawFd {
+        self.con.get_fd()
+    }
+}
+
+/// The window gravity of a bar docked to `dir`, keeping it at the edge.
+fn dock_gravity(dir: DockDirection) -> xproto::Gravity {
+    match dir {
+        DockDirection::Top => xproto::Gravity::North,
+        DockDirection::Bottom => xproto::Gravity::South,
+        DockDirection::Left => xproto::Gravity::West,
+        DockDirection::Right => xproto::Gravity::East,
     }
 }
 
+fn convert_pair<T: Into<i32>>((a, b): (T, T)) -> (i32, i32) {
+    (a.into(), b.into())
+}
+
+fn convert_width(width: u32) -> Result<u16, Error> {
+    width
+        .try_into()
+        .map_err(|e| Error::Custom(format!("invalid bar width ({})", e)))
+}
+
+fn convert_offset(offset: u32) -> Result<u16, Error> {
+    offset
+        .try_into()
+        .map_err(|e| Error::Custom(format!("invalid bar offset ({})", e)))
+}
+
+fn convert_margin(left: i32, right: i32) -> Result<(i16, i16), Error> {
+    left.try_into()
+        .and_then(|a| right.try_into().map(|b| (a, b)))
+        .map_err(|e| Error::Custom(format!("invalid bar outer margin ({})", e)))
+}
+
 fn filter_depth_visual_rgba(screen: &xproto::Screen) -> Option<(u8, &xproto::Visualtype)> {
     screen
         .allowed_depths
@@ -166,11 +456,23 @@ fn filter_depth_visual_rgba(screen: &xproto::Screen) -> Option<(u8, &xproto::Vis
         .find_map(|d| {
             d.visuals
                 .iter()
-                .find(|v| v.class == xproto::VisualClass::TrueColor && v.bits_per_rgb_value == 32)
+                .find(|v| v.class == xproto::VisualClass::TrueColor && v.bits_per_rgb_value == 8)
                 .map(|v| (d.depth, v))
         })
 }
 
+fn find_depth_visual(
+    screen: &xproto::Screen,
+    visual: xproto::Visualid,
+) -> Option<(u8, &xproto::Visualtype)> {
+    screen.allowed_depths.iter().find_map(|d| {
+        d.visuals
+            .iter()
+            .find(|v| v.visual_id == visual)
+            .map(|v| (d.depth, v))
+    })
+}
+
 impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
     for X11AdapterBar<'a, B, C>
 {
@@ -178,11 +480,16 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
         bar: &B,
         wm: &'a X11Adapter<B, C>,
         cfg: &BarBuilder,
-        screen: &xproto::Screen,
+        monitor: &X11Screen,
     ) -> Result<Self, Error> {
-        let geometry = wm.con.get_geometry(screen.root)?;
+        let screen = wm
+            .con
+            .setup()
+            .roots
+            .get(monitor.screen)
+            .ok_or_else(|| Error::Custom(format!("no X screen {}", monitor.screen)))?;
         let (visual, cw_values, depth_val) = if let (true, Some((depth, vis))) =
-            (cfg.get_transparency(), filter_depth_visual_rgba(&screen))
+            (cfg.get_transparency(), filter_depth_visual_rgba(screen))
         {
             let colormap = wm.con.generate_id()?;
             wm.con
@@ -204,22 +511,31 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
             (
                 screen.root_visual,
                 xproto::CreateWindowAux::new(),
-                x11::COPY_DEPTH_FROM_PARENT as u8,
+                x11::COPY_DEPTH_FROM_PARENT,
             )
         };
+        let format = match find_depth_visual(screen, visual) {
+            Some((depth, vis)) => PixelFormat::new(wm.con.setup(), depth, vis),
+            None => None,
+        }
+        .ok_or_else(|| Error::Custom("unsupported window visual".to_owned()))?;
         let win = wm.con.generate_id()?;
-        let geometry = geometry.reply()?;
-        let (sw, sh) = (geometry.width, geometry.height);
-        let (x, y) = (geometry.x, geometry.y);
-        let width = (*cfg.get_width())
-            .try_into()
-            .map_err(|e| Error::Custom(format!("invalid bar width ({})", e)))?;
-        let (w, h, grav) = match cfg.get_docking() {
-            DockDirection::Top => (sw, width, xproto::Gravity::North),
-            DockDirection::Bottom => (sw, width, xproto::Gravity::South),
-            DockDirection::Left => (width, sh, xproto::Gravity::West),
-            DockDirection::Right => (width, sh, xproto::Gravity::East),
-        };
+        let width = convert_width(*cfg.get_width())?;
+        let offset = convert_offset(*cfg.get_offset())?;
+        let (left, right) = convert_margin(*cfg.get_margin_left(), *cfg.get_margin_right())?;
+        let geometry = DockGeometry::new(
+            *cfg.get_docking(),
+            convert_pair(monitor.pos),
+            (monitor.size.0.into(), monitor.size.1.into()),
+            (monitor.root_size.0.into(), monitor.root_size.1.into()),
+            width.into(),
+            offset.into(),
+            convert_pair((left, right)),
+        );
+        // the geometry lies within the screen, so it fits the protocol
+        let (x, y) = (geometry.position.0 as i16, geometry.position.1 as i16);
+        let (w, h) = (geometry.size.0 as u16, geometry.size.1 as u16);
+        let grav = dock_gravity(*cfg.get_docking());
         let events = bar.get_event_types();
         let f = |a, b| {
             if events & a == 0 {
@@ -234,13 +550,18 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
             .background_pixel(screen.black_pixel)
             .event_mask(
                 xproto::EventMask::Exposure
+                    | xproto::EventMask::StructureNotify
                     | f(
-                        crate::event::CLICK,
+                        crate::event::CLICK | crate::event::SCROLL,
                         xproto::EventMask::ButtonPress | xproto::EventMask::ButtonRelease,
                     )
                     | f(
                         crate::event::MOUSE_MOVE,
-                        xproto::EventMask::ButtonMotion as u32,
+                        xproto::EventMask::PointerMotion as u32,
+                    )
+                    | f(
+                        crate::event::HOVER,
+                        xproto::EventMask::EnterWindow | xproto::EventMask::LeaveWindow,
                     ),
             );
         wm.con
@@ -259,53 +580,87 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
             )?
             .check()?;
 
-        let cookie1 = wm.con.map_window(win)?;
-        let cookie2 = wm.con.configure_window(
-            win,
-            &xproto::ConfigureWindowAux::new()
-                .x(Some(x.into()))
-                .y(Some(y.into()))
-                .width(Some(w.into()))
-                .height(Some(h.into())),
-        )?;
-
-        let (left, right): (i16, i16) = (*cfg.get_margin_left())
-            .try_into()
-            .and_then(|a| (*cfg.get_margin_right()).try_into().map(|b| (a, b)))
-            .map_err(|e| Error::Custom(format!("invalid bar outer margin ({})", e)))?;
+        let gc = wm.con.generate_id()?;
+        wm.con
+            .create_gc(
+                gc,
+                win,
+                &xproto::CreateGCAux::new().graphics_exposures(Some(0)),
+            )?
+            .check()?;
 
         let slf = Self {
             left,
             right,
-            pos: (x, y),
-            size: (w, h),
-            screen_size: (sw, sh),
+            docking: *cfg.get_docking(),
+            z_index: *cfg.get_z_index(),
+            root: monitor.root,
+            pos: monitor.pos,
+            screen_size: monitor.size,
+            root_size: monitor.root_size,
             dis: wm,
             win,
+            gc,
+            format,
+            shm: if wm.shm {
+                ShmState::Detached
+            } else {
+                ShmState::Unavailable
+            },
             width,
+            offset,
         };
 
+        wm.sizes.borrow_mut().insert(win, (w, h));
+
         let atoms = &slf.dis.atoms;
-        let docking_cookies = slf.set_docking_cookie(*cfg.get_docking())?;
+        let docking_cookies = slf.set_docking_cookie()?;
+        let protocols: &[xproto::Atom] = if events & event::QUIT != 0 {
+            &[atoms.WM_DELETE_WINDOW]
+        } else {
+            &[]
+        };
+        let class = format!("{0}\0{0}\0", cfg.get_class());
+        let hostname = crate::sys::hostname().unwrap_or_default();
+        let mut state = vec![atoms._NET_WM_STATE_STICKY, atoms._NET_WM_STATE_SKIP_TASKBAR];
+        state.extend(slf.z_index_state(slf.z_index));
         let cookies = [
             slf.change_property_atoms(
                 atoms._NET_WM_WINDOW_TYPE,
                 &[atoms._NET_WM_WINDOW_TYPE_DOCK],
             )?,
-            slf.change_property_atoms(
-                atoms._NET_WM_STATE,
-                &[
-                    atoms._NET_WM_STATE_STICKY,
-                    atoms._NET_WM_STATE_SKIP_TASKBAR,
-                    atoms._NET_WM_STATE_BELOW,
-                ],
-            )?,
+            slf.change_property_atoms(atoms._NET_WM_STATE, &state)?,
             slf.change_property_u32(atoms._NET_WM_DESKTOP, &[0xFFFFFFFF])?,
             slf.change_property_atoms(atoms._NET_WM_ALLOWED_ACTIONS, &[])?,
+            slf.change_property_atoms(atoms.WM_PROTOCOLS, protocols)?,
+            slf.change_property_string(
+                xproto::AtomEnum::WM_CLASS.into(),
+                xproto::AtomEnum::STRING.into(),
+                class.as_bytes(),
+            )?,
+            slf.change_property_string(
+                xproto::AtomEnum::WM_CLIENT_MACHINE.into(),
+                xproto::AtomEnum::STRING.into(),
+                hostname.as_bytes(),
+            )?,
+            slf.change_property_u32(atoms._NET_WM_PID, &[std::process::id()])?,
         ];
+        slf.await_void_cookies(slf.set_title_cookie(cfg.get_title())?)?;
         slf.await_void_cookies(docking_cookies)?;
         slf.await_void_cookies(cookies)?;
 
+        // window managers match their rules and read the initial state when
+        // the window is mapped, so everything has to be set before
+        let cookie1 = wm.con.map_window(win)?;
+        let cookie2 = wm.con.configure_window(
+            win,
+            &xproto::ConfigureWindowAux::new()
+                .x(Some(x.into()))
+                .y(Some(y.into()))
+                .width(Some(w.into()))
+                .height(Some(h.into())),
+        )?;
+
         cookie1.check()?;
         cookie2.check()?;
         wm.con.flush()?;
@@ -314,26 +669,253 @@ impl<'a, B: Bar, C: X11Connection> WmAdapterBar<'a, B, X11Adapter<B, C>>
     }
 
     fn set_docking(&mut self, dir: DockDirection) -> Result<(), Error> {
-        self.await_void_cookies(self.set_docking_cookie(dir)?)
+        self.docking = dir;
+        let grav = dock_gravity(dir);
+        self.dis
+            .con
+            .change_window_attributes(
+                self.win,
+                &xproto::ChangeWindowAttributesAux::new()
+                    .bit_gravity(grav)
+                    .win_gravity(grav),
+            )?
+            .check()?;
+        self.reconfigure()
+    }
+
+    fn get_docking(&self) -> DockDirection {
+        self.docking
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        self.await_void_cookies(self.set_title_cookie(title)?)
     }
 
-    fn set_margin(&mut self, _left: i32, _right: i32) -> Result<(), Error> {
-        unimplemented!("NIY")
+    fn set_z_index(&mut self, z_index: ZIndex) -> Result<(), Error> {
+        let atoms = &self.dis.atoms;
+        // once mapped, the state belongs to the window manager and can only
+        // be changed by asking it
+        let remove =
+            self.send_state_message(0, atoms._NET_WM_STATE_ABOVE, atoms._NET_WM_STATE_BELOW)?;
+        let add = match self.z_index_state(z_index) {
+            Some(state) => Some(self.send_state_message(1, state, x11::NONE)?),
+            None => None,
+        };
+        remove.check()?;
+        if let Some(add) = add {
+            add.check()?;
+        }
+        self.z_index = z_index;
+        Ok(())
     }
 
-    fn blit(&mut self, _surface: &Surface, _x: i32, _y: i32) -> Result<(), Error> {
-        unimplemented!("NIY")
+    fn set_screen(&mut self, monitor: &X11Screen) -> Result<(), Error> {
+        self.pos = monitor.pos;
+        self.screen_size = monitor.size;
+        self.root_size = monitor.root_size;
+        self.reconfigure()
+    }
+
+    fn set_margin(&mut self, left: i32, right: i32) -> Result<(), Error> {
+        let (left, right) = convert_margin(left, right)?;
+        self.left = left;
+        self.right = right;
+        self.reconfigure()
+    }
+
+    fn set_thickness(&mut self, thickness: u32) -> Result<(), Error> {
+        self.width = convert_width(thickness)?;
+        self.reconfigure()
+    }
+
+    fn set_offset(&mut self, offset: u32) -> Result<(), Error> {
+        self.offset = convert_offset(offset)?;
+        self.reconfigure()
+    }
+
+    fn blit(&mut self, surface: &Surface, x: i32, y: i32) -> Result<(), Error> {
+        let (sw, sh) = (surface.width() as i32, surface.height() as i32);
+        let (ww, wh) = self.window_size();
+        let (ww, wh) = (ww as i32, wh as i32);
+        let (sx, sy) = ((-x).max(0), (-y).max(0));
+        let (dx, dy) = (x.max(0), y.max(0));
+        let w = (sw - sx).min(ww - dx);
+        let h = (sh - sy).min(wh - dy);
+        self.put_image_any(surface, (sx, sy), (dx, dy), (w, h))
+    }
+
+    fn blit_rect(&mut self, surface: &Surface, rect: Rect) -> Result<(), Error> {
+        let (ww, wh) = self.window_size();
+        let rect = rect
+            .intersect(&Rect::new(0, 0, surface.width(), surface.height()))
+            .intersect(&Rect::new(0, 0, ww.into(), wh.into()));
+        let (x, y) = (rect.x, rect.y);
+        self.put_image_any(
+            surface,
+            (x, y),
+            (x, y),
+            (rect.width as i32, rect.height as i32),
+        )
+    }
+
+    fn get_size(&self) -> (u32, u32) {
+        let (w, h) = self.window_size();
+        (w.into(), h.into())
+    }
+
+    fn get_id(&self) -> event::BarId {
+        self.win
+    }
+
+    fn destroy(self) -> Result<(), Error> {
+        self.dis.sizes.borrow_mut().remove(&self.win);
+        if self.dis.closed.get() {
+            return Ok(());
+        }
+        let con = &self.dis.con;
+        if let ShmState::Attached(seg) = self.shm {
+            seg.destroy()?;
+        }
+        con.free_gc(self.gc)?;
+        con.destroy_window(self.win)?.check()?;
+        Ok(())
     }
 }
 
 impl<'a, B: Bar, C: X11Connection> X11AdapterBar<'a, B, C> {
+    fn put_image_any(
+        &mut self,
+        surface: &Surface,
+        src: (i32, i32),
+        dst: (i32, i32),
+        (w, h): (i32, i32),
+    ) -> Result<(), Error> {
+        if w <= 0 || h <= 0 {
+            return Ok(());
+        }
+        if !self.put_image_shm(surface, src, dst, (w, h))? {
+            self.put_image(surface, src, dst, (w, h))?;
+        }
+        Ok(())
+    }
+
+    fn put_image(
+        &self,
+        surface: &Surface,
+        (sx, sy): (i32, i32),
+        (dx, dy): (i32, i32),
+        (w, h): (i32, i32),
+    ) -> Result<(), Error> {
+        let stride = self.format.stride(w as u16);
+        let max_rows = (self.dis.con.maximum_request_bytes() - PUT_IMAGE_HEADER) / stride;
+        if max_rows == 0 {
+            return Err(Error::Custom(
+                "bar too wide for a single request".to_owned(),
+            ));
+        }
+        let mut buf = vec![0; stride * max_rows.min(h as usize)];
+        let mut cookies = Vec::new();
+        let mut row = 0;
+        while row < h {
+            let rows = (max_rows as i32).min(h - row);
+            let data = &mut buf[..stride * rows as usize];
+            for (r, out) in (row..row + rows).zip(data.chunks_exact_mut(stride)) {
+                let line = surface.row((sy + r) as u32);
+                self.format
+                    .encode_row(&line[sx as usize..(sx + w) as usize], out);
+            }
+            cookies.push(self.dis.con.put_image(
+                xproto::ImageFormat::ZPixmap,
+                self.win,
+                self.gc,
+                w as u16,
+                rows as u16,
+                dx as i16,
+                (dy + row) as i16,
+                0,
+                self.format.depth,
+                data,
+            )?);
+            row += rows;
+        }
+        for cookie in cookies {
+            cookie.check()?;
+        }
+        Ok(())
+    }
+
+    fn put_image_shm(
+        &mut self,
+        surface: &Surface,
+        (sx, sy): (i32, i32),
+        (dx, dy): (i32, i32),
+        (w, h): (i32, i32),
+    ) -> Result<bool, Error> {
+        let stride = self.format.stride(w as u16);
+        let size = stride * h as usize;
+        let needs_alloc = match &self.shm {
+            ShmState::Unavailable => return Ok(false),
+            ShmState::Detached => true,
+            ShmState::Attached(seg) => seg.size() < size,
+        };
+        if needs_alloc {
+            if let ShmState::Attached(seg) =
+                core::mem::replace(&mut self.shm, ShmState::Unavailable)
+            {
+                seg.destroy()?;
+            }
+            let (ww, wh) = self.window_size();
+            let full = self.format.stride(ww) * wh as usize;
+            match ShmSegment::new(&self.dis.con, size.max(full))? {
+                Some(seg) => self.shm = ShmState::Attached(seg),
+                None => return Ok(false),
+            }
+        }
+        let seg = match &mut self.shm {
+            ShmState::Attached(seg) => seg,
+            _ => return Ok(false),
+        };
+        // the server may still be reading the previous frame
+        self.dis.await_shm(seg.seg())?;
+        for (r, out) in (0..h).zip(seg.data_mut().chunks_exact_mut(stride)) {
+            let line = surface.row((sy + r) as u32);
+            self.format
+                .encode_row(&line[sx as usize..(sx + w) as usize], out);
+        }
+        // the server reports with a completion event when it is done reading
+        // the segment, so the next blit doesn't wait for a round trip
+        let cookie = self.dis.con.shm_put_image(
+            self.win,
+            self.gc,
+            w as u16,
+            h as u16,
+            0,
+            0,
+            w as u16,
+            h as u16,
+            dx as i16,
+            dy as i16,
+            self.format.depth,
+            xproto::ImageFormat::ZPixmap.into(),
+            true,
+            seg.seg(),
+            0,
+        )?;
+        // errors of the request are reported as events
+        let seq = cookie.sequence_number() as u16;
+        drop(cookie);
+        self.dis.shm_busy.borrow_mut().insert(seg.seg(), seq);
+        Ok(true)
+    }
+
     fn await_void_cookies<const N: usize>(
         &self,
         cookies: [x11::cookie::VoidCookie<'a, C>; N],
     ) -> Result<(), Error> {
-        Ok(for cookie in core::array::IntoIter::new(cookies) {
+        for cookie in IntoIterator::into_iter(cookies) {
             cookie.check()?;
-        })
+        }
+        Ok(())
     }
     fn change_property_u32(
         &self,
@@ -380,25 +962,107 @@ impl<'a, B: Bar, C: X11Connection> X11AdapterBar<'a, B, C> {
             serialize_u32(values),
         )?)
     }
-    fn set_docking_cookie(
+    fn window_size(&self) -> (u16, u16) {
+        self.dis
+            .sizes
+            .borrow()
+            .get(&self.win)
+            .copied()
+            .unwrap_or((1, 1))
+    }
+    /// Moves and resizes the window to match the current configuration and
+    /// updates the struts.
+    fn reconfigure(&mut self) -> Result<(), Error> {
+        let geometry = self.geometry();
+        let cookie = self.dis.con.configure_window(
+            self.win,
+            &xproto::ConfigureWindowAux::new()
+                .x(Some(geometry.position.0))
+                .y(Some(geometry.position.1))
+                .width(Some(geometry.size.0))
+                .height(Some(geometry.size.1)),
+        )?;
+        self.await_void_cookies(self.set_docking_cookie()?)?;
+        cookie.check()?;
+        Ok(())
+    }
+    fn change_property_string(
+        &self,
+        key: xproto::Atom,
+        type_: xproto::Atom,
+        value: &[u8],
+    ) -> Result<x11::cookie::VoidCookie<'a, C>, Error> {
+        Ok(self.dis.con.change_property(
+            xproto::PropMode::Replace,
+            self.win,
+            key,
+            type_,
+            8,
+            value.len() as u32,
+            value,
+        )?)
+    }
+    fn set_title_cookie(&self, title: &str) -> Result<[x11::cookie::VoidCookie<'a, C>; 2], Error> {
+        // WM_NAME is Latin-1, so non-ASCII titles only survive in _NET_WM_NAME
+        let latin1: Vec<u8> = title
+            .chars()
+            .map(|c| u32::from(c).try_into().unwrap_or(b'?'))
+            .collect();
+        Ok([
+            self.change_property_string(
+                xproto::AtomEnum::WM_NAME.into(),
+                xproto::AtomEnum::STRING.into(),
+                &latin1,
+            )?,
+            self.change_property_string(
+                self.dis.atoms._NET_WM_NAME,
+                self.dis.atoms.UTF8_STRING,
+                title.as_bytes(),
+            )?,
+        ])
+    }
+    fn z_index_state(&self, z_index: ZIndex) -> Option<xproto::Atom> {
+        match z_index {
+            ZIndex::AboveEverything => Some(self.dis.atoms._NET_WM_STATE_ABOVE),
+            ZIndex::Normal => None,
+            ZIndex::BelowEverything => Some(self.dis.atoms._NET_WM_STATE_BELOW),
+        }
+    }
+    fn send_state_message(
         &self,
-        dir: DockDirection,
-    ) -> Result<[x11::cookie::VoidCookie<'a, C>; 2], Error> {
-        let ((x, y), (sw, sh), width, left, right) = (
-            self.pos,
-            self.screen_size,
-            self.width,
-            self.left,
-            self.right,
-        );
-        #[allow(clippy::deprecated_cfg_attr)]
-        #[cfg_attr(rustfmt, rustfmt_skip)]
-        let strut_args: [i32; 12] = match dir {
-            DockDirection::Bottom => [0, 0, 0, width.into(), 0, 0, 0, 0, 0, 0, (x + left).into(), (x + (sw as i16) - right).into()],
-            DockDirection::Top => [0, 0, width.into(), 0, 0, 0, 0, 0, (x + left).into(), (x + (sw as i16) - right).into(), 0, 0],
-            DockDirection::Right => [0, width.into(), 0, 0, 0, 0, (y + left).into(), (y + (sh as i16) - right).into(), 0, 0, 0, 0],
-            DockDirection::Left => [width.into(), 0, 0, 0, (y + left).into(), (y + (sh as i16) - right).into(), 0, 0, 0, 0, 0, 0],
+        action: u32,
+        first: xproto::Atom,
+        second: xproto::Atom,
+    ) -> Result<x11::cookie::VoidCookie<'a, C>, Error> {
+        let event = xproto::ClientMessageEvent {
+            response_type: xproto::CLIENT_MESSAGE_EVENT,
+            format: 32,
+            sequence: 0,
+            window: self.win,
+            type_: self.dis.atoms._NET_WM_STATE,
+            // the trailing 1 marks the request as coming from an application
+            data: [action, first, second, 1, 0].into(),
         };
+        Ok(self.dis.con.send_event(
+            false,
+            self.root,
+            xproto::EventMask::SubstructureRedirect | xproto::EventMask::SubstructureNotify,
+            event,
+        )?)
+    }
+    fn geometry(&self) -> DockGeometry {
+        DockGeometry::new(
+            self.docking,
+            convert_pair(self.pos),
+            (self.screen_size.0.into(), self.screen_size.1.into()),
+            (self.root_size.0.into(), self.root_size.1.into()),
+            self.width.into(),
+            self.offset.into(),
+            convert_pair((self.left, self.right)),
+        )
+    }
+    fn set_docking_cookie(&self) -> Result<[x11::cookie::VoidCookie<'a, C>; 2], Error> {
+        let strut_args = self.geometry().strut;
         Ok([
             self.change_property_i32(self.dis.atoms._NET_WM_STRUT, &strut_args[..4])?,
             self.change_property_i32(self.dis.atoms._NET_WM_STRUT_PARTIAL, &strut_args)?,
@@ -0,0 +1,170 @@
+use neo_bar::bar::{
+    run_with, Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmScreen, WmSurface,
+};
+use neo_bar::config::{BarBuilder, BarInstance, DockDirection};
+use neo_bar::draw::{Color, Painter, Rect};
+use neo_bar::event::{self, ClickEvent, Event, MouseButton};
+use neo_bar::headless::{HeadlessAdapter, HeadlessScreen};
+use neo_bar::surface::Surface;
+
+#[derive(Default)]
+struct FillBar {
+    draws: usize,
+    resizes: Vec<(u32, u32)>,
+}
+
+impl Bar for FillBar {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn select_screens<S: WmScreen>(&self, screens: &[S]) -> Vec<usize> {
+        (0..screens.len())
+            .filter(|&i| screens[i].is_primary())
+            .collect()
+    }
+
+    fn get_bar_builder(&self) -> BarBuilder {
+        BarBuilder::default()
+            .docking(DockDirection::Bottom)
+            .margin(10, 20)
+            .width(30)
+    }
+
+    fn get_event_types(&self) -> event::EventTypes {
+        event::CLICK
+    }
+
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _damage: Rect,
+    ) {
+        let (w, h) = bar.get_size();
+        let mut surface = Surface::new(w, h);
+        Painter::new(&mut surface).clear(Color::rgb(0x11, 0x22, 0x33));
+        let surface = <Wm as WmAdapter<Self>>::Surface::from_surface(surface);
+        bar.blit_rect(&surface, Rect::new(0, 0, w, h)).unwrap();
+        self.draws += 1;
+    }
+
+    fn on_resize<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        _bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        event: event::ResizeEvent,
+    ) {
+        self.resizes.push((event.width, event.height));
+    }
+
+    fn on_click<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        event: ClickEvent,
+    ) {
+        if event.pressed {
+            bar.set_docking(DockDirection::Left).unwrap();
+        }
+    }
+}
+
+fn screens() -> Vec<HeadlessScreen> {
+    vec![
+        HeadlessScreen::new(0, 0, 1280, 1024).named("LEFT"),
+        HeadlessScreen::new(1280, 0, 1920, 1080)
+            .named("RIGHT")
+            .primary(true),
+    ]
+}
+
+fn click(bar: event::BarId) -> Event {
+    Event::MouseDown(ClickEvent {
+        bar,
+        x: 5,
+        y: 5,
+        root_x: 5,
+        root_y: 5,
+        button: MouseButton::Left,
+        pressed: true,
+        modifiers: 0,
+        time: 0,
+    })
+}
+
+#[test]
+fn places_bar_and_struts() {
+    let wm = HeadlessAdapter::with_screens(screens()).unwrap();
+    let bar = run_with(FillBar::new(), &wm).unwrap();
+    let bars = wm.bars();
+    assert_eq!(bars.len(), 1);
+    let state = &bars[0];
+    assert_eq!(state.screen.name(), Some("RIGHT"));
+    assert_eq!(state.position, (1290, 1050));
+    assert_eq!(state.size, (1890, 30));
+    assert_eq!(state.strut, [0, 0, 0, 30, 0, 0, 0, 0, 0, 0, 1290, 3179]);
+    assert!(state.destroyed);
+    assert_eq!(bar.draws, 1);
+    let expected = Color::rgb(0x11, 0x22, 0x33).premultiplied();
+    assert!(state.pixels.data().iter().all(|&px| px == expected));
+}
+
+#[test]
+fn scripted_events_reach_the_bar() {
+    let wm = HeadlessAdapter::with_screens(screens()).unwrap();
+    wm.push_event(click(1));
+    let bar = run_with(FillBar::new(), &wm).unwrap();
+    let state = wm.bar(1).unwrap();
+    assert_eq!(state.docking, DockDirection::Left);
+    assert_eq!(state.size, (30, 1050));
+    assert_eq!(bar.resizes, vec![(30, 1050)]);
+    assert_eq!(state.pixels.width(), 30);
+}
+
+#[test]
+fn screen_changes_move_bars() {
+    let wm = HeadlessAdapter::with_screens(screens()).unwrap();
+    wm.push_screens(vec![
+        HeadlessScreen::new(0, 0, 1280, 1024)
+            .named("LEFT")
+            .primary(true),
+        HeadlessScreen::new(1280, 0, 1920, 1080).named("RIGHT"),
+    ]);
+    run_with(FillBar::new(), &wm).unwrap();
+    let bars = wm.bars();
+    assert_eq!(bars.len(), 2);
+    assert!(bars[0].destroyed);
+    assert_eq!(bars[1].screen.name(), Some("LEFT"));
+    assert_eq!(bars[1].position, (10, 994));
+    // the right monitor is taller, so the strut reaches above it
+    assert_eq!(bars[1].strut[3], 56 + 30);
+}
+
+/// A bar that asks for an invalid second bar once there are two screens.
+struct InvalidSecondBar;
+
+impl Bar for InvalidSecondBar {
+    fn new() -> Self {
+        Self
+    }
+
+    fn get_bar_instances<S: WmScreen>(&self, screens: &[S]) -> Vec<BarInstance> {
+        let builder = BarBuilder::default().width(20);
+        if screens.len() == 1 {
+            vec![BarInstance::new(0, builder)]
+        } else {
+            vec![
+                BarInstance::new(1, builder.clone().width(0)),
+                BarInstance::new(0, builder),
+            ]
+        }
+    }
+}
+
+#[test]
+fn failed_screen_changes_destroy_bars() {
+    let wm = HeadlessAdapter::with_screens(screens()[..1].to_vec()).unwrap();
+    wm.push_screens(screens());
+    assert!(run_with(InvalidSecondBar, &wm).is_err());
+    let bars = wm.bars();
+    assert_eq!(bars.len(), 1);
+    assert!(bars[0].destroyed);
+}
@@ -0,0 +1,87 @@
+#![cfg(feature = "snapshot")]
+
+use neo_bar::bar::{Bar, WmAdapterExt, WmAdapterGetBar};
+use neo_bar::config::{BarBuilder, DockDirection};
+use neo_bar::draw::{Color, Painter, Rect};
+use neo_bar::module::{Module, ModuleContainer, Section};
+use neo_bar::snapshot::{self, Error, Snapshot};
+use neo_bar::surface::Surface;
+
+struct Block(Color, u32);
+
+impl Module for Block {
+    fn measure(&mut self, _thickness: u32) -> u32 {
+        self.1
+    }
+
+    fn draw(&mut self, painter: &mut Painter<Surface>) {
+        let rect = painter.bounds().inset(3);
+        painter.fill_rounded_rect(rect, 4, self.0);
+        painter.underline(rect, 2, Color::WHITE);
+    }
+}
+
+struct ModuleBar {
+    modules: ModuleContainer,
+}
+
+impl Bar for ModuleBar {
+    fn new() -> Self {
+        let mut modules = ModuleContainer::new(DockDirection::Top)
+            .padding(2)
+            .spacing(4)
+            .background(Color::rgba(0x20, 0x20, 0x20, 0xc0));
+        modules.push(Section::Left, Block(Color::rgb(0x73, 0x27, 0xf3), 40));
+        modules.push(Section::Center, Block(Color::rgb(0xa3, 0x11, 0x11), 30));
+        modules.push(Section::Right, Block(Color::rgb(0x11, 0xa3, 0x11), 20));
+        Self { modules }
+    }
+
+    fn get_bar_builder(&self) -> BarBuilder {
+        BarBuilder::default().docking(DockDirection::Top).width(24)
+    }
+
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        damage: Rect,
+    ) {
+        self.modules.render_damage::<Self, Wm>(bar, damage).unwrap();
+    }
+}
+
+fn snapshots() -> Snapshot {
+    Snapshot::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots"))
+}
+
+#[test]
+fn renders_modules() {
+    let surface = snapshot::render(ModuleBar::new(), 200, 100).unwrap();
+    assert_eq!((surface.width(), surface.height()), (200, 24));
+    snapshots().check("modules", &surface).unwrap();
+}
+
+#[test]
+fn reports_differences() {
+    let mut surface = snapshot::render(ModuleBar::new(), 200, 100).unwrap();
+    surface.set_pixel(0, 0, Color::WHITE.premultiplied());
+    surface.set_pixel(100, 12, Color::BLACK.premultiplied());
+    let dir = std::env::temp_dir().join(format!("neo-bar-snapshot-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let reference = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/snapshots/modules.png");
+    std::fs::copy(reference, dir.join("modules.png")).unwrap();
+    match Snapshot::new(&dir).check("modules", &surface) {
+        Err(Error::Mismatch { pixels, diff }) => {
+            assert_eq!(pixels, 2);
+            assert!(diff.exists());
+        }
+        res => panic!("expected a mismatch, got {:?}", res),
+    }
+    // the black pixel on the red block is too far off for the tolerance
+    surface.set_pixel(0, 0, Color::rgba(0x20, 0x20, 0x20, 0xc0).premultiplied());
+    assert!(Snapshot::new(&dir)
+        .tolerance(0x40)
+        .check("modules", &surface)
+        .is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
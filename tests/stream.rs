@@ -0,0 +1,104 @@
+#![cfg(feature = "async")]
+
+use core::cell::RefCell;
+use core::task::{Context, Poll, Waker};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use async_io::Timer;
+use neo_bar::bar::{Bar, WmAdapter, WmAdapterBar, WmAdapterExt, WmAdapterGetBar, WmSurface};
+use neo_bar::draw::{Color, Painter, Rect};
+use neo_bar::event::Event;
+use neo_bar::headless::HeadlessAdapter;
+use neo_bar::module::Update;
+use neo_bar::stream::run_async_with;
+use neo_bar::surface::Surface;
+
+/// A single-slot channel, standing in for a socket or subprocess the bar
+/// reads from.
+#[derive(Default)]
+struct Channel {
+    value: Option<u8>,
+    waker: Option<Waker>,
+}
+
+impl Channel {
+    fn send(channel: &RefCell<Self>, value: u8) {
+        let mut channel = channel.borrow_mut();
+        channel.value = Some(value);
+        if let Some(waker) = channel.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[derive(Default)]
+struct FeedBar {
+    channel: Rc<RefCell<Channel>>,
+    value: u8,
+    drawn: Vec<u8>,
+}
+
+fn color(value: u8) -> Color {
+    Color::rgb(value, 0, 0)
+}
+
+impl Bar for FeedBar {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<Update> {
+        let mut channel = self.channel.borrow_mut();
+        match channel.value.take() {
+            Some(value) => {
+                self.value = value;
+                Poll::Ready(Update::Redraw)
+            }
+            None => {
+                channel.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    fn on_draw<'a, Wm: WmAdapterExt<Self>>(
+        &mut self,
+        bar: &mut <Wm as WmAdapterGetBar<'a, Self>>::AdapterBar,
+        _damage: Rect,
+    ) {
+        let (w, h) = bar.get_size();
+        let mut surface = Surface::new(w, h);
+        Painter::new(&mut surface).clear(color(self.value));
+        let surface = <Wm as WmAdapter<Self>>::Surface::from_surface(surface);
+        bar.blit_rect(&surface, Rect::new(0, 0, w, h)).unwrap();
+        self.drawn.push(self.value);
+    }
+}
+
+#[test]
+fn async_source_triggers_redraw() {
+    let wm = <HeadlessAdapter as WmAdapter<FeedBar>>::new(&Default::default())
+        .unwrap()
+        .quit_when_idle(false);
+    let bar = FeedBar::default();
+    let channel = bar.channel.clone();
+    let feed = async {
+        Timer::after(Duration::from_millis(10)).await;
+        Channel::send(&channel, 42);
+        // the runner sleeps until the bar's waker is woken, so the new
+        // frame shows up without any window event
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let expected = color(42).premultiplied();
+        while Instant::now() < deadline {
+            let bars = wm.bars();
+            if bars[0].pixels.get_pixel(0, 0) == Some(expected) {
+                break;
+            }
+            Timer::after(Duration::from_millis(1)).await;
+        }
+        wm.push_event(Event::Quit);
+    };
+    let (bar, ()) = async_io::block_on(futures_lite::future::zip(run_async_with(bar, &wm), feed));
+    assert_eq!(bar.unwrap().drawn, [0, 42]);
+}
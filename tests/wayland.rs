@@ -0,0 +1,779 @@
+//! Runs the Wayland adapter against a minimal compositor in a thread, which
+//! implements just enough of wl_compositor, wl_shm, wl_output and
+//! wlr-layer-shell to record the layer surface state the adapter commits.
+
+#![cfg(feature = "wm-wayland")]
+
+use core::task::Poll;
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::UnixListener;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use neo_bar::bar::{run_with, Bar, WmAdapter, WmAdapterBar, WmScreen};
+use neo_bar::config::{BarBuilder, BarInstance, DockDirection, ZIndex};
+use neo_bar::event::{BarId, Event};
+use neo_bar::wayland::{WaylandAdapterBar, WaylandScreen};
+use wayland_protocols_wlr::layer_shell::v1::server::{zwlr_layer_shell_v1, zwlr_layer_surface_v1};
+use wayland_server::backend::GlobalId;
+use wayland_server::protocol::{
+    wl_buffer, wl_compositor, wl_output, wl_region, wl_shm, wl_shm_pool, wl_surface,
+};
+use wayland_server::{
+    Client, DataInit, Dispatch, Display, DisplayHandle, GlobalDispatch, New, Resource, WEnum,
+};
+
+use zwlr_layer_shell_v1::Layer;
+use zwlr_layer_surface_v1::Anchor;
+
+type Adapter = neo_bar::wayland::WaylandAdapter<TestBar>;
+
+#[derive(Debug, Clone)]
+struct OutputInfo {
+    name: &'static str,
+    pos: (i32, i32),
+    mode: (i32, i32),
+    scale: i32,
+}
+
+const HDMI: OutputInfo = OutputInfo {
+    name: "HDMI-A-1",
+    pos: (0, 0),
+    mode: (1920, 1080),
+    scale: 1,
+};
+
+const DP: OutputInfo = OutputInfo {
+    name: "DP-1",
+    pos: (1920, 0),
+    mode: (2560, 1440),
+    scale: 2,
+};
+
+/// The state of a layer surface as of its last commit.
+#[derive(Debug, Clone, Default)]
+struct LayerState {
+    output: Option<&'static str>,
+    layer: Option<Layer>,
+    anchor: Option<Anchor>,
+    size: (u32, u32),
+    /// Top, right, bottom and left.
+    margin: (i32, i32, i32, i32),
+    exclusive_zone: i32,
+    buffer_scale: i32,
+    /// The size sent with the last configure event.
+    configured: Option<(u32, u32)>,
+    commits: u32,
+}
+
+enum Command {
+    AddOutput(OutputInfo),
+    RemoveOutput(&'static str),
+}
+
+/// The state shared with the test.
+#[derive(Default)]
+struct Shared {
+    layers: HashMap<BarId, LayerState>,
+    commands: Vec<Command>,
+}
+
+/// The state of the compositor thread.
+struct Server {
+    shared: Arc<Mutex<Shared>>,
+    outputs: Vec<(OutputInfo, GlobalId)>,
+    /// The uncommitted state of the layer surfaces, by surface id.
+    pending: HashMap<BarId, LayerState>,
+    roles: HashMap<BarId, zwlr_layer_surface_v1::ZwlrLayerSurfaceV1>,
+    buffers: HashMap<BarId, wl_buffer::WlBuffer>,
+    scales: HashMap<BarId, i32>,
+    serial: u32,
+}
+
+impl Server {
+    fn run_commands(&mut self, dh: &DisplayHandle) {
+        let commands = core::mem::take(&mut self.shared.lock().unwrap().commands);
+        for command in commands {
+            match command {
+                Command::AddOutput(info) => {
+                    let global = dh.create_global::<Self, wl_output::WlOutput, _>(4, info.clone());
+                    self.outputs.push((info, global));
+                }
+                Command::RemoveOutput(name) => {
+                    let i = self
+                        .outputs
+                        .iter()
+                        .position(|(o, _)| o.name == name)
+                        .unwrap();
+                    dh.remove_global::<Self>(self.outputs.remove(i).1);
+                }
+            }
+        }
+    }
+
+    fn logical_size(&self, output: Option<&str>) -> (u32, u32) {
+        self.outputs
+            .iter()
+            .find(|(o, _)| Some(o.name) == output)
+            .map_or((0, 0), |(o, _)| {
+                ((o.mode.0 / o.scale) as u32, (o.mode.1 / o.scale) as u32)
+            })
+    }
+
+    fn commit(&mut self, id: BarId) {
+        if let Some(buffer) = self.buffers.remove(&id) {
+            buffer.release();
+        }
+        let mut state = match self.pending.get(&id) {
+            Some(state) => state.clone(),
+            None => return,
+        };
+        state.buffer_scale = self.scales.get(&id).copied().unwrap_or(1);
+        // a zero size stretches the surface between the anchored edges
+        let (ow, oh) = self.logical_size(state.output);
+        let (top, right, bottom, left) = state.margin;
+        let size = (
+            if state.size.0 == 0 {
+                (ow as i32 - left - right) as u32
+            } else {
+                state.size.0
+            },
+            if state.size.1 == 0 {
+                (oh as i32 - top - bottom) as u32
+            } else {
+                state.size.1
+            },
+        );
+        let mut shared = self.shared.lock().unwrap();
+        let previous = shared.layers.get(&id);
+        state.commits = previous.map_or(0, |s| s.commits) + 1;
+        if state.configured != Some(size) {
+            self.serial += 1;
+            self.roles[&id].configure(self.serial, size.0, size.1);
+            state.configured = Some(size);
+        }
+        self.pending.insert(id, state.clone());
+        shared.layers.insert(id, state);
+    }
+}
+
+impl GlobalDispatch<wl_compositor::WlCompositor, ()> for Server {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_compositor::WlCompositor>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<wl_compositor::WlCompositor, ()> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_compositor::WlCompositor,
+        request: wl_compositor::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        match request {
+            wl_compositor::Request::CreateSurface { id } => {
+                data_init.init(id, ());
+            }
+            wl_compositor::Request::CreateRegion { id } => {
+                data_init.init(id, ());
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<wl_region::WlRegion, ()> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_region::WlRegion,
+        _: wl_region::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl Dispatch<wl_surface::WlSurface, ()> for Server {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        surface: &wl_surface::WlSurface,
+        request: wl_surface::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        let id = surface.id().protocol_id();
+        match request {
+            wl_surface::Request::Attach {
+                buffer: Some(buffer),
+                ..
+            } => {
+                state.buffers.insert(id, buffer);
+            }
+            wl_surface::Request::SetBufferScale { scale } => {
+                state.scales.insert(id, scale);
+            }
+            wl_surface::Request::Commit => state.commit(id),
+            _ => {}
+        }
+    }
+}
+
+impl GlobalDispatch<wl_shm::WlShm, ()> for Server {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_shm::WlShm>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let shm = data_init.init(resource, ());
+        shm.format(wl_shm::Format::Argb8888);
+        shm.format(wl_shm::Format::Xrgb8888);
+    }
+}
+
+impl Dispatch<wl_shm::WlShm, ()> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_shm::WlShm,
+        request: wl_shm::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm::Request::CreatePool { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<wl_shm_pool::WlShmPool, ()> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_shm_pool::WlShmPool,
+        request: wl_shm_pool::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let wl_shm_pool::Request::CreateBuffer { id, .. } = request {
+            data_init.init(id, ());
+        }
+    }
+}
+
+impl Dispatch<wl_buffer::WlBuffer, ()> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_buffer::WlBuffer,
+        _: wl_buffer::Request,
+        _: &(),
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<wl_output::WlOutput, OutputInfo> for Server {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<wl_output::WlOutput>,
+        info: &OutputInfo,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        let output = data_init.init(resource, info.name);
+        output.geometry(
+            info.pos.0,
+            info.pos.1,
+            600,
+            340,
+            wl_output::Subpixel::Unknown,
+            "neo-bar".to_owned(),
+            info.name.to_owned(),
+            wl_output::Transform::Normal,
+        );
+        output.mode(wl_output::Mode::Current, info.mode.0, info.mode.1, 60000);
+        output.scale(info.scale);
+        output.name(info.name.to_owned());
+        output.done();
+    }
+}
+
+impl Dispatch<wl_output::WlOutput, &'static str> for Server {
+    fn request(
+        _: &mut Self,
+        _: &Client,
+        _: &wl_output::WlOutput,
+        _: wl_output::Request,
+        _: &&'static str,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+    }
+}
+
+impl GlobalDispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for Server {
+    fn bind(
+        _: &mut Self,
+        _: &DisplayHandle,
+        _: &Client,
+        resource: New<zwlr_layer_shell_v1::ZwlrLayerShellV1>,
+        _: &(),
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        data_init.init(resource, ());
+    }
+}
+
+impl Dispatch<zwlr_layer_shell_v1::ZwlrLayerShellV1, ()> for Server {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &zwlr_layer_shell_v1::ZwlrLayerShellV1,
+        request: zwlr_layer_shell_v1::Request,
+        _: &(),
+        _: &DisplayHandle,
+        data_init: &mut DataInit<'_, Self>,
+    ) {
+        if let zwlr_layer_shell_v1::Request::GetLayerSurface {
+            id,
+            surface,
+            output,
+            layer,
+            ..
+        } = request
+        {
+            let bar = surface.id().protocol_id();
+            let role = data_init.init(id, bar);
+            let output = output.and_then(|o| o.data::<&'static str>().copied());
+            let layer = match layer {
+                WEnum::Value(layer) => Some(layer),
+                WEnum::Unknown(_) => None,
+            };
+            state.roles.insert(bar, role);
+            state.pending.insert(
+                bar,
+                LayerState {
+                    output,
+                    layer,
+                    ..LayerState::default()
+                },
+            );
+        }
+    }
+}
+
+impl Dispatch<zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, BarId> for Server {
+    fn request(
+        state: &mut Self,
+        _: &Client,
+        _: &zwlr_layer_surface_v1::ZwlrLayerSurfaceV1,
+        request: zwlr_layer_surface_v1::Request,
+        bar: &BarId,
+        _: &DisplayHandle,
+        _: &mut DataInit<'_, Self>,
+    ) {
+        let pending = match state.pending.get_mut(bar) {
+            Some(pending) => pending,
+            None => return,
+        };
+        match request {
+            zwlr_layer_surface_v1::Request::SetSize { width, height } => {
+                pending.size = (width, height);
+            }
+            zwlr_layer_surface_v1::Request::SetAnchor {
+                anchor: WEnum::Value(anchor),
+            } => pending.anchor = Some(anchor),
+            zwlr_layer_surface_v1::Request::SetExclusiveZone { zone } => {
+                pending.exclusive_zone = zone;
+            }
+            zwlr_layer_surface_v1::Request::SetMargin {
+                top,
+                right,
+                bottom,
+                left,
+            } => pending.margin = (top, right, bottom, left),
+            zwlr_layer_surface_v1::Request::SetLayer {
+                layer: WEnum::Value(layer),
+            } => pending.layer = Some(layer),
+            zwlr_layer_surface_v1::Request::Destroy => {
+                state.pending.remove(bar);
+                state.roles.remove(bar);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// A compositor running in a thread, stopped when dropped.
+struct Compositor {
+    shared: Arc<Mutex<Shared>>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+    socket: PathBuf,
+}
+
+impl Compositor {
+    fn spawn(outputs: &[OutputInfo]) -> Self {
+        static SOCKETS: AtomicUsize = AtomicUsize::new(0);
+        let socket = std::env::temp_dir().join(format!(
+            "neo-bar-test-{}-{}",
+            std::process::id(),
+            SOCKETS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let listener = UnixListener::bind(&socket).expect("failed to bind the socket");
+        listener.set_nonblocking(true).unwrap();
+        let shared = Arc::new(Mutex::new(Shared::default()));
+        shared.lock().unwrap().commands = outputs.iter().cloned().map(Command::AddOutput).collect();
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let (shared, stop) = (shared.clone(), stop.clone());
+            std::thread::spawn(move || serve(listener, shared, &stop))
+        };
+        Self {
+            shared,
+            stop,
+            thread: Some(thread),
+            socket,
+        }
+    }
+
+    fn connect(&self) -> Adapter {
+        Adapter::connect(Some(self.socket.to_str().unwrap())).unwrap()
+    }
+
+    fn send(&self, command: Command) {
+        self.shared.lock().unwrap().commands.push(command);
+    }
+
+    fn layer(&self, bar: BarId) -> LayerState {
+        self.shared.lock().unwrap().layers[&bar].clone()
+    }
+
+    /// Waits until `count` layer surfaces were committed and returns them in
+    /// creation order.
+    fn await_layers(&self, count: usize) -> Vec<LayerState> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let mut layers: Vec<_> = self
+                .shared
+                .lock()
+                .unwrap()
+                .layers
+                .clone()
+                .into_iter()
+                .collect();
+            if layers.len() >= count {
+                layers.sort_by_key(|&(id, _)| id);
+                return layers.into_iter().map(|(_, layer)| layer).collect();
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for layers");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// Waits for the commit following the state `before` was taken at.
+    fn next_commit(&self, bar: BarId, before: &LayerState) -> LayerState {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            let layer = self.layer(bar);
+            if layer.commits > before.commits {
+                return layer;
+            }
+            assert!(Instant::now() < deadline, "timed out waiting for a commit");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+impl Drop for Compositor {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        let _ = std::fs::remove_file(&self.socket);
+    }
+}
+
+fn serve(listener: UnixListener, shared: Arc<Mutex<Shared>>, stop: &AtomicBool) {
+    let mut display = Display::<Server>::new().unwrap();
+    let dh = display.handle();
+    dh.create_global::<Server, wl_compositor::WlCompositor, _>(4, ());
+    dh.create_global::<Server, wl_shm::WlShm, _>(1, ());
+    dh.create_global::<Server, zwlr_layer_shell_v1::ZwlrLayerShellV1, _>(4, ());
+    let mut server = Server {
+        shared,
+        outputs: Vec::new(),
+        pending: HashMap::new(),
+        roles: HashMap::new(),
+        buffers: HashMap::new(),
+        scales: HashMap::new(),
+        serial: 0,
+    };
+    while !stop.load(Ordering::Relaxed) {
+        if let Ok((stream, _)) = listener.accept() {
+            display
+                .handle()
+                .insert_client(stream, Arc::new(()))
+                .unwrap();
+        }
+        server.run_commands(&dh);
+        display.dispatch_clients(&mut server).unwrap();
+        display.flush_clients().unwrap();
+        let mut fd = libc::pollfd {
+            fd: display.backend().poll_fd().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        unsafe { libc::poll(&mut fd, 1, 5) };
+    }
+}
+
+struct TestBar;
+
+impl Bar for TestBar {
+    fn new() -> Self {
+        Self
+    }
+}
+
+type TestAdapterBar<'a> = WaylandAdapterBar<'a, TestBar>;
+
+fn builder(dir: DockDirection) -> BarBuilder {
+    BarBuilder::default()
+        .docking(dir)
+        .margin(10, 20)
+        .offset(5)
+        .width(30)
+}
+
+fn screen(wm: &Adapter, n: usize) -> WaylandScreen {
+    WmAdapter::<TestBar>::get_screen(wm, n).unwrap()
+}
+
+/// Waits for the next event of the adapter.
+fn next_event(wm: &Adapter) -> Event {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        if let Poll::Ready(ev) = WmAdapter::<TestBar>::poll_event(wm).unwrap() {
+            return ev;
+        }
+        assert!(Instant::now() < deadline, "timed out waiting for an event");
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn await_screens_changed(wm: &Adapter) {
+    while !matches!(next_event(wm), Event::ScreensChanged) {}
+}
+
+/// The anchor, requested size, margin and configured size of a bar docked
+/// to `dir` of the HDMI output.
+fn expected_layer(dir: DockDirection) -> LayerState {
+    let (w, h) = (HDMI.mode.0 as u32, HDMI.mode.1 as u32);
+    let (anchor, size, margin, configured) = match dir {
+        DockDirection::Top => (
+            Anchor::Top | Anchor::Left | Anchor::Right,
+            (0, 30),
+            (5, 20, 0, 10),
+            (w - 30, 30),
+        ),
+        DockDirection::Bottom => (
+            Anchor::Bottom | Anchor::Left | Anchor::Right,
+            (0, 30),
+            (0, 20, 5, 10),
+            (w - 30, 30),
+        ),
+        DockDirection::Left => (
+            Anchor::Left | Anchor::Top | Anchor::Bottom,
+            (30, 0),
+            (10, 0, 20, 5),
+            (30, h - 30),
+        ),
+        DockDirection::Right => (
+            Anchor::Right | Anchor::Top | Anchor::Bottom,
+            (30, 0),
+            (10, 5, 20, 0),
+            (30, h - 30),
+        ),
+    };
+    LayerState {
+        anchor: Some(anchor),
+        size,
+        margin,
+        configured: Some(configured),
+        ..LayerState::default()
+    }
+}
+
+fn assert_docked(layer: &LayerState, dir: DockDirection) {
+    let expected = expected_layer(dir);
+    assert_eq!(layer.anchor, expected.anchor, "{:?}", dir);
+    assert_eq!(layer.size, expected.size, "{:?}", dir);
+    assert_eq!(layer.margin, expected.margin, "{:?}", dir);
+    assert_eq!(layer.configured, expected.configured, "{:?}", dir);
+    assert_eq!(layer.exclusive_zone, 30, "{:?}", dir);
+}
+
+const DIRECTIONS: [DockDirection; 4] = [
+    DockDirection::Top,
+    DockDirection::Bottom,
+    DockDirection::Left,
+    DockDirection::Right,
+];
+
+#[test]
+fn docked_bars_anchor_and_reserve_space() {
+    let compositor = Compositor::spawn(&[HDMI]);
+    let wm = compositor.connect();
+    let screen = screen(&wm, 0);
+    assert_eq!(screen.name(), Some(HDMI.name));
+    for &dir in DIRECTIONS.iter() {
+        let bar: TestAdapterBar<'_> =
+            WmAdapterBar::new(&TestBar, &wm, &builder(dir), &screen).unwrap();
+        let layer = compositor.layer(bar.get_id());
+        assert_docked(&layer, dir);
+        assert_eq!(layer.output, Some(HDMI.name));
+        assert_eq!(layer.buffer_scale, 1);
+        assert_eq!(Some(bar.get_size()), layer.configured);
+        bar.destroy().unwrap();
+    }
+}
+
+#[test]
+fn set_docking_moves_the_anchors() {
+    let compositor = Compositor::spawn(&[HDMI]);
+    let wm = compositor.connect();
+    let mut bar: TestAdapterBar<'_> =
+        WmAdapterBar::new(&TestBar, &wm, &builder(DockDirection::Top), &screen(&wm, 0)).unwrap();
+    let id = bar.get_id();
+    for &dir in DIRECTIONS.iter().rev() {
+        let before = compositor.layer(id);
+        bar.set_docking(dir).unwrap();
+        assert_docked(&compositor.next_commit(id, &before), dir);
+    }
+    bar.destroy().unwrap();
+}
+
+#[test]
+fn layer_follows_z_index() {
+    let compositor = Compositor::spawn(&[HDMI]);
+    let wm = compositor.connect();
+    let screen = screen(&wm, 0);
+    let layers = [
+        (ZIndex::AboveEverything, Layer::Overlay),
+        (ZIndex::Normal, Layer::Top),
+        (ZIndex::BelowEverything, Layer::Bottom),
+    ];
+    for &(z_index, layer) in layers.iter() {
+        let cfg = builder(DockDirection::Top).z_index(z_index);
+        let bar: TestAdapterBar<'_> = WmAdapterBar::new(&TestBar, &wm, &cfg, &screen).unwrap();
+        assert_eq!(compositor.layer(bar.get_id()).layer, Some(layer));
+        bar.destroy().unwrap();
+    }
+    let mut bar: TestAdapterBar<'_> =
+        WmAdapterBar::new(&TestBar, &wm, &builder(DockDirection::Top), &screen).unwrap();
+    let id = bar.get_id();
+    for &(z_index, layer) in layers.iter().rev() {
+        let before = compositor.layer(id);
+        bar.set_z_index(z_index).unwrap();
+        assert_eq!(compositor.next_commit(id, &before).layer, Some(layer));
+    }
+    bar.destroy().unwrap();
+}
+
+#[test]
+fn outputs_can_be_hotplugged() {
+    let compositor = Compositor::spawn(&[HDMI]);
+    let wm = compositor.connect();
+    assert_eq!(WmAdapter::<TestBar>::get_screen_count(&wm), 1);
+    let mut bar: TestAdapterBar<'_> =
+        WmAdapterBar::new(&TestBar, &wm, &builder(DockDirection::Top), &screen(&wm, 0)).unwrap();
+
+    compositor.send(Command::AddOutput(DP));
+    await_screens_changed(&wm);
+    assert_eq!(WmAdapter::<TestBar>::get_screen_count(&wm), 2);
+    let dp = screen(&wm, 1);
+    assert_eq!(dp.name(), Some(DP.name));
+    assert_eq!(dp.position(), DP.pos);
+    assert_eq!(dp.dimensions(), (1280, 720));
+    assert_eq!(dp.scale(), 2);
+    assert!(screen(&wm, 0).is_primary() && !dp.is_primary());
+
+    // layer surfaces can't change their output, so the bar gets a new one
+    bar.set_screen(&dp).unwrap();
+    let layer = compositor.layer(bar.get_id());
+    assert_eq!(layer.output, Some(DP.name));
+    assert_eq!(layer.configured, Some((1280 - 30, 30)));
+    // the bar is drawn at the output's resolution
+    assert_eq!(layer.buffer_scale, 2);
+    assert_eq!(bar.get_size(), (2 * (1280 - 30), 2 * 30));
+
+    compositor.send(Command::RemoveOutput(DP.name));
+    await_screens_changed(&wm);
+    assert_eq!(WmAdapter::<TestBar>::get_screen_count(&wm), 1);
+    assert_eq!(screen(&wm, 0).name(), Some(HDMI.name));
+    bar.destroy().unwrap();
+}
+
+/// Two bars on the top edge of the first output.
+struct StackedBars;
+
+impl Bar for StackedBars {
+    fn new() -> Self {
+        Self
+    }
+
+    fn get_bar_instances<S: WmScreen>(&self, _screens: &[S]) -> Vec<BarInstance> {
+        let builder = builder(DockDirection::Top);
+        vec![
+            BarInstance::new(0, builder.clone()),
+            BarInstance::new(0, builder),
+        ]
+    }
+}
+
+#[test]
+fn compositor_stacks_bars_on_the_same_edge() {
+    let compositor = Compositor::spawn(&[HDMI]);
+    let socket = compositor.socket.clone();
+    let runner = std::thread::spawn(move || {
+        let wm = neo_bar::wayland::WaylandAdapter::<StackedBars>::connect(socket.to_str())?;
+        run_with(StackedBars, &wm).map(drop)
+    });
+    let layers = compositor.await_layers(2);
+    // the compositor puts the second bar after the exclusive zone of the
+    // first, so both only keep the offset of the builder
+    for layer in layers.iter() {
+        assert_eq!(layer.margin, (5, 20, 0, 10));
+        assert_eq!(layer.exclusive_zone, 30);
+    }
+    // closing the connection makes the bar quit
+    drop(compositor);
+    runner.join().unwrap().unwrap();
+}
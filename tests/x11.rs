@@ -0,0 +1,237 @@
+//! Runs the X11 adapter against a private Xvfb server. The tests need
+//! `Xvfb` and are ignored by default, run them with `cargo test -- --ignored`.
+
+#![cfg(feature = "wm-x11-rust")]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+
+use neo_bar::bar::{Bar, WmAdapter, WmAdapterBar, WmScreen};
+use neo_bar::config::{BarBuilder, DockDirection, ZIndex};
+use neo_bar::x11::X11AdapterBar;
+use x11rb::connection::Connection;
+use x11rb::protocol::xproto::{self, ConnectionExt};
+use x11rb::rust_connection::RustConnection;
+
+type Adapter = neo_bar::x11::X11RustAdapter<DockBar>;
+
+const SCREEN: (u32, u32) = (1280, 1024);
+const SECOND_SCREEN: (u32, u32) = (800, 600);
+
+/// An Xvfb server that is killed when dropped.
+struct Xvfb {
+    child: Child,
+    display: String,
+}
+
+impl Xvfb {
+    /// Starts Xvfb with one X screen per size on a free display number and
+    /// waits until it accepts connections.
+    fn spawn(screens: &[(u32, u32)]) -> Self {
+        // Xvfb picks the display number itself and writes it to stdout once
+        // it is ready, so parallel tests can't race for the same number
+        let mut command = Command::new("Xvfb");
+        command.args(["-displayfd", "1"]);
+        for (n, (w, h)) in screens.iter().enumerate() {
+            command
+                .arg("-screen")
+                .arg(n.to_string())
+                .arg(format!("{}x{}x24", w, h));
+        }
+        let mut child = command
+            .args(["-nolisten", "tcp"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("failed to start Xvfb");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .expect("failed to read the display number from Xvfb");
+        let n: u32 = match line.trim().parse() {
+            Ok(n) => n,
+            Err(_) => {
+                let _ = child.kill();
+                panic!("Xvfb exited without a display: {:?}", child.wait());
+            }
+        };
+        Self {
+            child,
+            display: format!(":{}", n),
+        }
+    }
+
+    fn connect(&self) -> RustConnection {
+        RustConnection::connect(Some(&self.display))
+            .expect("failed to connect to Xvfb")
+            .0
+    }
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+struct DockBar;
+
+impl Bar for DockBar {
+    fn new() -> Self {
+        Self
+    }
+}
+
+fn builder(dir: DockDirection) -> BarBuilder {
+    BarBuilder::default()
+        .docking(dir)
+        .margin(10, 20)
+        .width(30)
+        .z_index(ZIndex::AboveEverything)
+}
+
+fn atom(con: &RustConnection, name: &str) -> xproto::Atom {
+    con.intern_atom(false, name.as_bytes())
+        .unwrap()
+        .reply()
+        .unwrap()
+        .atom
+}
+
+fn property_u32(con: &RustConnection, win: xproto::Window, name: &str) -> Vec<u32> {
+    con.get_property(false, win, atom(con, name), xproto::AtomEnum::Any, 0, 64)
+        .unwrap()
+        .reply()
+        .unwrap()
+        .value32()
+        .expect("not a 32 bit property")
+        .collect()
+}
+
+fn geometry(con: &RustConnection, win: xproto::Window) -> (i16, i16, u16, u16) {
+    let geo = con.get_geometry(win).unwrap().reply().unwrap();
+    (geo.x, geo.y, geo.width, geo.height)
+}
+
+fn expected_strut(dir: DockDirection) -> [u32; 12] {
+    let (w, h) = SCREEN;
+    match dir {
+        DockDirection::Top => [0, 0, 30, 0, 0, 0, 0, 0, 10, w - 21, 0, 0],
+        DockDirection::Bottom => [0, 0, 0, 30, 0, 0, 0, 0, 0, 0, 10, w - 21],
+        DockDirection::Left => [30, 0, 0, 0, 10, h - 21, 0, 0, 0, 0, 0, 0],
+        DockDirection::Right => [0, 30, 0, 0, 0, 0, 10, h - 21, 0, 0, 0, 0],
+    }
+}
+
+fn expected_geometry(dir: DockDirection) -> (i16, i16, u16, u16) {
+    let (w, h) = (SCREEN.0 as u16, SCREEN.1 as u16);
+    match dir {
+        DockDirection::Top => (10, 0, w - 30, 30),
+        DockDirection::Bottom => (10, h as i16 - 30, w - 30, 30),
+        DockDirection::Left => (0, 10, 30, h - 30),
+        DockDirection::Right => (w as i16 - 30, 10, 30, h - 30),
+    }
+}
+
+fn assert_docked(con: &RustConnection, win: xproto::Window, dir: DockDirection) {
+    let strut = expected_strut(dir);
+    assert_eq!(property_u32(con, win, "_NET_WM_STRUT_PARTIAL"), strut);
+    assert_eq!(property_u32(con, win, "_NET_WM_STRUT"), strut[..4]);
+    assert_eq!(geometry(con, win), expected_geometry(dir));
+}
+
+const DIRECTIONS: [DockDirection; 4] = [
+    DockDirection::Top,
+    DockDirection::Bottom,
+    DockDirection::Left,
+    DockDirection::Right,
+];
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn docked_bars_set_ewmh_properties() {
+    let xvfb = Xvfb::spawn(&[SCREEN]);
+    let con = xvfb.connect();
+    let wm = Adapter::connect(Some(&xvfb.display)).unwrap();
+    let screen = WmAdapter::<DockBar>::get_screen(&wm, 0).unwrap();
+    assert_eq!(screen.dimensions(), SCREEN);
+    let dock = atom(&con, "_NET_WM_WINDOW_TYPE_DOCK");
+    let state = [
+        atom(&con, "_NET_WM_STATE_STICKY"),
+        atom(&con, "_NET_WM_STATE_SKIP_TASKBAR"),
+        atom(&con, "_NET_WM_STATE_ABOVE"),
+    ];
+    for &dir in DIRECTIONS.iter() {
+        let bar: X11AdapterBar<'_, DockBar, RustConnection> =
+            WmAdapterBar::new(&DockBar, &wm, &builder(dir), &screen).unwrap();
+        let win = bar.get_id();
+        assert_docked(&con, win, dir);
+        assert_eq!(property_u32(&con, win, "_NET_WM_WINDOW_TYPE"), [dock]);
+        assert_eq!(property_u32(&con, win, "_NET_WM_STATE"), state);
+        assert_eq!(property_u32(&con, win, "_NET_WM_DESKTOP"), [0xffffffff]);
+        bar.destroy().unwrap();
+    }
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn set_docking_moves_bar_and_struts() {
+    let xvfb = Xvfb::spawn(&[SCREEN]);
+    let con = xvfb.connect();
+    let wm = Adapter::connect(Some(&xvfb.display)).unwrap();
+    let screen = WmAdapter::<DockBar>::get_screen(&wm, 0).unwrap();
+    let mut bar: X11AdapterBar<'_, DockBar, RustConnection> =
+        WmAdapterBar::new(&DockBar, &wm, &builder(DockDirection::Top), &screen).unwrap();
+    for &dir in DIRECTIONS.iter().rev() {
+        bar.set_docking(dir).unwrap();
+        assert_docked(&con, bar.get_id(), dir);
+        assert_eq!(bar.get_docking(), dir);
+    }
+    bar.destroy().unwrap();
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn builder_selects_display() {
+    let (first, second) = (Xvfb::spawn(&[SCREEN]), Xvfb::spawn(&[SCREEN]));
+    for xvfb in [&first, &second].iter() {
+        let cfg = builder(DockDirection::Bottom).display_owned(xvfb.display.clone());
+        let wm = <Adapter as WmAdapter<DockBar>>::new(&cfg).unwrap();
+        let screen = WmAdapter::<DockBar>::get_screen(&wm, 0).unwrap();
+        assert!(screen.is_primary());
+        let bar: X11AdapterBar<'_, DockBar, RustConnection> =
+            WmAdapterBar::new(&DockBar, &wm, &cfg, &screen).unwrap();
+        assert_docked(&xvfb.connect(), bar.get_id(), DockDirection::Bottom);
+        bar.destroy().unwrap();
+    }
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn builder_selects_screen() {
+    let xvfb = Xvfb::spawn(&[SCREEN, SECOND_SCREEN]);
+    let cfg = builder(DockDirection::Bottom).display_owned(format!("{}.1", xvfb.display));
+    let wm = <Adapter as WmAdapter<DockBar>>::new(&cfg).unwrap();
+    assert_eq!(wm.get_screen_number(), 1);
+    let screens: Vec<_> = (0..WmAdapter::<DockBar>::get_screen_count(&wm))
+        .map(|n| WmAdapter::<DockBar>::get_screen(&wm, n).unwrap())
+        .collect();
+    assert!(screens.iter().any(|s| s.get_screen_number() == 0));
+    let primary: Vec<_> = screens.iter().filter(|s| s.is_primary()).collect();
+    assert_eq!(primary.len(), 1);
+    assert_eq!(primary[0].get_screen_number(), 1);
+    assert_eq!(primary[0].dimensions(), SECOND_SCREEN);
+
+    let bar: X11AdapterBar<'_, DockBar, RustConnection> =
+        WmAdapterBar::new(&DockBar, &wm, &cfg, primary[0]).unwrap();
+    let con = xvfb.connect();
+    let geo = con.get_geometry(bar.get_id()).unwrap().reply().unwrap();
+    assert_eq!(geo.root, con.setup().roots[1].root);
+    let (w, h) = (SECOND_SCREEN.0 as u16, SECOND_SCREEN.1 as u16);
+    assert_eq!(
+        (geo.x, geo.y, geo.width, geo.height),
+        (10, h as i16 - 30, w - 30, 30)
+    );
+    bar.destroy().unwrap();
+}